@@ -3,7 +3,7 @@ use colored::{Colorize, ColoredString};
 use std::hint::black_box;
 use lazy_static::lazy_static;
 
-use crate::engine::*;
+use crate::{engine::*, PKG_VERSION};
 
 lazy_static!(
     pub static ref POSITIONS: Vec<(i8, Position)> = vec![
@@ -25,7 +25,7 @@ lazy_static!(
 const ITERATIONS: u16 = 1;
 
 /// Base depth for search bench
-const BASE_DEPTH: u8 = 9;
+pub const BASE_DEPTH: u8 = 9;
 
 pub fn run_bench(save: bool) {
     println!();
@@ -164,6 +164,115 @@ pub fn run_bench(save: bool) {
     println!()
 }
 
+/// Searches the bench positions single threaded from a fresh hash table, and prints the total node count and speed.
+/// The node count only changes when the search does, so it can be used as a signature by testing frameworks like OpenBench.
+/// Each position is searched to the base depth adjusted by its bias
+pub fn run_node_bench(base_depth: u8) {
+    let (results, millis) = node_bench(base_depth);
+    let nodes: u128 = results.iter().map(|res| res.nodes).sum();
+    println!("{nodes} nodes {} nps", nodes * 1000 / millis);
+}
+
+/// The base depth of the node bench, or BASE_DEPTH if none is given
+pub fn parse_bench_depth(arg: Option<&str>) -> Result<u8, String> {
+    match arg.map(|depth| depth.parse::<u8>()) {
+        None => Ok(BASE_DEPTH),
+        Some(Ok(depth)) if (1..=MAX_DEPTH).contains(&depth) => Ok(depth),
+        Some(_) => Err(format!("Illegal bench depth. Expected a number from 1 to {MAX_DEPTH}")),
+    }
+}
+
+/// Runs the node bench, and prints the totals and the statistics of each position as JSON.
+/// The rates are fractions from 0 to 1
+pub fn run_json_bench(base_depth: u8) {
+    let (results, millis) = node_bench(base_depth);
+    let total: SearchStats = results.iter().copied().sum();
+
+    let positions: Vec<String> = results.iter().map(|res| format!("    {}", stats_json(res, res.time))).collect();
+    println!("{{");
+    println!("  \"version\": \"{PKG_VERSION}\",");
+    println!("  \"base_depth\": {base_depth},");
+    println!("  \"total\": {},", stats_json(&total, millis));
+    println!("  \"positions\": [\n{}\n  ]", positions.join(",\n"));
+    println!("}}");
+}
+
+/// Searches each bench position single threaded with a fresh hash table. Returns the results and the total millis
+fn node_bench(base_depth: u8) -> (Vec<SearchStats>, u128) {
+    let before = Instant::now();
+
+    let results = POSITIONS.iter().map(|(bias, pos)| {
+        let search = Search::new(Settings::default().threads(1));
+        let depth = (base_depth as i16 + *bias as i16).clamp(1, MAX_DEPTH as i16) as u8;
+        search.start(*pos, SearchArgs::new_simple_depth(depth), false, CONST_EVALUATOR)
+    }).collect();
+
+    (results, before.elapsed().as_millis().max(1))
+}
+
+fn stats_json(stats: &SearchStats, millis: u128) -> String {
+    let counters = &stats.counters;
+    let fields = [
+        format!("\"nodes\": {}", stats.nodes),
+        format!("\"time_ms\": {millis}"),
+        format!("\"nps\": {}", stats.nodes * 1000 / millis.max(1)),
+        format!("\"depth\": {}", stats.depth),
+        format!("\"qnodes\": {}", counters.qnodes),
+        format!("\"qnode_share\": {:.4}", stats.qnode_share()),
+        format!("\"tt_probes\": {}", counters.tt_probes),
+        format!("\"tt_hits\": {}", stats.tt_hits),
+        format!("\"tt_hit_rate\": {:.4}", stats.tt_hit_rate()),
+        format!("\"tt_collisions\": {}", counters.tt_collisions),
+        format!("\"tt_collision_rate\": {:.4}", stats.tt_collision_rate()),
+        format!("\"beta_cutoffs\": {}", counters.beta_cutoffs),
+        format!("\"beta_cutoff_rate\": {:.4}", stats.beta_cutoff_rate()),
+        format!("\"first_move_cutoff_rate\": {:.4}", stats.first_move_cutoff_rate()),
+        format!("\"null_move_tries\": {}", counters.null_move_tries),
+        format!("\"null_move_success_rate\": {:.4}", stats.null_move_success_rate()),
+        format!("\"lmr_searches\": {}", counters.lmr_searches),
+        format!("\"lmr_success_rate\": {:.4}", stats.lmr_success_rate()),
+        format!("\"branching_factor\": {:.4}", stats.branching_factor()),
+    ];
+    format!("{{ {} }}", fields.join(", "))
+}
+
+/// Searches the bench positions at every skill level, to calibrate the strength limits.
+/// Prints the average depth and nodes, and how often the move played is the move found at full strength
+pub fn run_skill_bench() {
+    println!();
+    println!(" Finding full strength moves...");
+
+    let best_moves: Vec<_> = POSITIONS.iter().map(|(bias, pos)| {
+        let search = Search::new(Settings::default());
+        let meta = SearchArgs::new_simple_depth((BASE_DEPTH as i8 + bias) as u8);
+        search.start(*pos, meta, false, CONST_EVALUATOR).best_move
+    }).collect();
+
+    println!();
+    println!(" Level    Depth      Nodes    Best move");
+
+    for level in 0..MAX_SKILL_LEVEL {
+        let mut depth = 0;
+        let mut nodes = 0;
+        let mut agreements = 0;
+
+        for ((_, pos), best_move) in POSITIONS.iter().zip(&best_moves) {
+            let search = Search::new(Settings::default().skill_level(level));
+            let res = search.start(*pos, SearchArgs::new_simple_depth(MAX_DEPTH), false, CONST_EVALUATOR);
+            depth += res.depth as u128;
+            nodes += res.nodes;
+            if res.best_move == *best_move {
+                agreements += 1;
+            }
+        }
+
+        let count = POSITIONS.len() as u128;
+        println!(" {level:>5} {:>8.1} {:>10} {:>11.0}%", depth as f64 / count as f64, nodes / count, agreements as f64 * 100. / count as f64);
+    }
+
+    println!()
+}
+
 fn color_string_percent(str: ColoredString, percent: f64, increase_is_desired: bool) -> ColoredString {
     if percent < -0.5 {
         if increase_is_desired {
@@ -3,7 +3,7 @@ use std::{fmt::Display, mem};
 use Color::*;
 
 #[repr(u8)]
-#[derive(Clone, Copy, PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum Color {
     White = 0,
     Black = 1,
@@ -0,0 +1,272 @@
+use std::fmt::Display;
+
+use super::*;
+
+use Color::*;
+use PieceType::*;
+
+/// Rook squares a castling right needs, along with the king square and the right's FEN char
+const CASTLING_SQUARES: [(CastlingSide, char, u8, u8); 4] = [
+    (CastlingSide::WhiteKingSide, 'K', 60, 63),
+    (CastlingSide::WhiteQueenSide, 'Q', 60, 56),
+    (CastlingSide::BlackKingSide, 'k', 4, 7),
+    (CastlingSide::BlackQueenSide, 'q', 4, 0),
+];
+
+/// The 75 move rule ends the game when the 50 move rule counter reaches this
+const MAX_HALF_MOVES: u16 = 150;
+
+/// How a FEN of a position that can not occur in a game is handled
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FenMode {
+    /// Reject the position
+    Strict,
+    /// Drop impossible castling rights and en passant squares,
+    /// and allow more pieces than a game can have, or a 50 move rule counter past the 75 move rule. Positions the engine can not search are still rejected
+    Lenient,
+}
+
+/// Why a FEN was rejected
+#[derive(Clone, Debug, PartialEq)]
+pub enum FenError {
+    MissingBoard,
+    MissingColor,
+    UnexpectedField(String),
+    /// The board does not have 8 ranks
+    WrongRankCount(usize),
+    /// The rank does not have 8 squares
+    WrongRankLength(u8),
+    IllegalPieceChar(char),
+    IllegalColor(String),
+    IllegalCastling(String),
+    IllegalEnPassant(String),
+    IllegalHalfMoves(String),
+    IllegalFullMoves(String),
+    KingCount(Color, u64),
+    PawnOnBackRank(Square),
+    TooManyPawns(Color),
+    TooManyPieces(Color),
+    /// The side not to move is in check, so its king could be captured
+    OpponentInCheck,
+    /// The king or rook needed for the castling right is not on its starting square
+    ImpossibleCastling(char),
+    /// No pawn can just have made a double push to the en passant square
+    ImpossibleEnPassant(Square),
+    HalfMovesTooLarge(u16),
+}
+
+impl Display for FenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FenError::MissingBoard => write!(f, "Expected board string in fen"),
+            FenError::MissingColor => write!(f, "Expected a color char"),
+            FenError::UnexpectedField(field) => write!(f, "Unexpected field after the full move counter: '{field}'"),
+            FenError::WrongRankCount(ranks) => write!(f, "Board must have 8 ranks, but had {ranks}"),
+            FenError::WrongRankLength(rank) => write!(f, "Rank {rank} does not have 8 squares"),
+            FenError::IllegalPieceChar(char) => write!(f, "Illegal piece char: '{char}'"),
+            FenError::IllegalColor(color) => write!(f, "Illegal color string: '{color}'. Expected 'w' or 'b'"),
+            FenError::IllegalCastling(castling) => write!(f, "Illegal castling ability string: '{castling}'"),
+            FenError::IllegalEnPassant(square) => write!(f, "Illegal en passant square: '{square}'"),
+            FenError::IllegalHalfMoves(half_moves) => write!(f, "Half moves was not a number: '{half_moves}'"),
+            FenError::IllegalFullMoves(full_moves) => write!(f, "Full moves was not a number: '{full_moves}'"),
+            FenError::KingCount(color, count) => write!(f, "{color} must have exactly one king, but has {count}"),
+            FenError::PawnOnBackRank(square) => write!(f, "Pawn on the back rank at {square}"),
+            FenError::TooManyPawns(color) => write!(f, "{color} has more than 8 pawns"),
+            FenError::TooManyPieces(color) => write!(f, "{color} has more than 16 pieces"),
+            FenError::OpponentInCheck => write!(f, "The side not to move is in check"),
+            FenError::ImpossibleCastling(right) => write!(f, "Castling right '{right}' without the king and rook on their starting squares"),
+            FenError::ImpossibleEnPassant(square) => write!(f, "En passant square {square} is not behind a pawn that just moved two squares"),
+            FenError::HalfMovesTooLarge(half_moves) => write!(f, "Half moves is {half_moves}, but can be at most {MAX_HALF_MOVES}"),
+        }
+    }
+}
+
+impl From<FenError> for String {
+    fn from(err: FenError) -> Self {
+        err.to_string()
+    }
+}
+
+impl Position {
+    /// Parses a FEN, normalizing positions that can not occur in a game when possible
+    pub fn from_fen(input: &str) -> Result<Self, FenError> {
+        Self::parse_fen(input, FenMode::Lenient)
+    }
+
+    /// Parses a FEN, rejecting positions that can not occur in a game
+    pub fn from_fen_strict(input: &str) -> Result<Self, FenError> {
+        Self::parse_fen(input, FenMode::Strict)
+    }
+
+    pub fn parse_fen(input: &str, mode: FenMode) -> Result<Self, FenError> {
+        let mut fields = input.split_whitespace();
+
+        let board_str = fields.next().ok_or(FenError::MissingBoard)?;
+
+        // Active color
+        let active_color = match fields.next() {
+            Some(color_str) => Color::from_str(color_str).map_err(|_| FenError::IllegalColor(color_str.to_string()))?,
+            None => return Err(FenError::MissingColor),
+        };
+
+        // Castling ability
+        let castling_str = fields.next().unwrap_or("-");
+        let castling_ability = CastlingAbility::from_str(castling_str).map_err(|_| FenError::IllegalCastling(castling_str.to_string()))?;
+
+        // Enpassant square
+        let mut enpassant_square = Bitboard::EMPTY;
+        match fields.next() {
+            None | Some("-") => (),
+            Some(enp_str) => match Square::from_str(enp_str) {
+                Ok(square) => enpassant_square.set_bit(square as u8),
+                Err(_) => return Err(FenError::IllegalEnPassant(enp_str.to_string())),
+            },
+        };
+
+        // 50 move rule count
+        let half_moves = match fields.next() {
+            Some(hm_str) => hm_str.parse().map_err(|_| FenError::IllegalHalfMoves(hm_str.to_string()))?,
+            None => 0,
+        };
+
+        // Full moves
+        let full_moves = match fields.next() {
+            Some(fm_str) => fm_str.parse().map_err(|_| FenError::IllegalFullMoves(fm_str.to_string()))?,
+            None => 1,
+        };
+
+        if let Some(field) = fields.next() {
+            return Err(FenError::UnexpectedField(field.to_string()))
+        }
+
+        // Init position with empty board
+        let mut pos = Position {
+            bitboards: [Bitboard::EMPTY; 12],
+            color_occupancies: [Bitboard::EMPTY; 2],
+            all_occupancies: Bitboard::EMPTY,
+            piece_squares: [Empty; 64],
+            active_color,
+            enpassant_square_bitboard: enpassant_square,
+            castling_ability,
+            full_moves,
+            half_moves,
+            zobrist_hash: 0,
+            rep_table: RepetitionTable::new(),
+        };
+
+        // Place pieces, rank by rank from the 8th
+        let ranks: Vec<&str> = board_str.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongRankCount(ranks.len()))
+        }
+
+        for (rank_index, rank_str) in ranks.into_iter().enumerate() {
+            let rank = 8 - rank_index as u8;
+            let mut file = 0;
+            for char in rank_str.chars() {
+                if let Some(empty) = char.to_digit(10).filter(|d| (1..=8).contains(d)) {
+                    file += empty as u8;
+                } else {
+                    let (color, piece_type) = char_to_piece(char).map_err(|_| FenError::IllegalPieceChar(char))?;
+                    if file >= 8 {
+                        return Err(FenError::WrongRankLength(rank))
+                    }
+                    pos.place_piece(color, piece_type, rank_index as u8 * 8 + file);
+                    file += 1;
+                }
+
+                if file > 8 {
+                    return Err(FenError::WrongRankLength(rank))
+                }
+            }
+
+            if file != 8 {
+                return Err(FenError::WrongRankLength(rank))
+            }
+        }
+
+        pos.validate(mode)?;
+
+        // Initialize zobrist
+        pos.generate_zobrist_hash();
+        pos.rep_table.push(pos.zobrist_hash);
+
+        Ok(pos)
+    }
+
+    /// Checks that the position can occur in a game. In lenient mode, the castling rights and en passant square
+    /// are corrected instead of rejected
+    fn validate(&mut self, mode: FenMode) -> Result<(), FenError> {
+        let strict = mode == FenMode::Strict;
+
+        // The engine can not search positions with a wrong number of kings, pawns it can not move, or a capturable king
+        for color in [White, Black] {
+            let kings = self.bb(color, King).pop_count();
+            if kings != 1 {
+                return Err(FenError::KingCount(color, kings))
+            }
+        }
+
+        let back_rank_pawns = (self.bb(White, Pawn) | self.bb(Black, Pawn)).as_u64() & (TOP_RANK | BOTTOM_RANK);
+        if back_rank_pawns != 0 {
+            return Err(FenError::PawnOnBackRank(Square::from(back_rank_pawns.trailing_zeros() as u8)))
+        }
+
+        let opponent = self.active_color.opposite();
+        if self.is_square_attacked(self.king_position(opponent), self.active_color) {
+            return Err(FenError::OpponentInCheck)
+        }
+
+        // Promotions can not give a side more than 16 pieces, or more than 8 pawns
+        for color in [White, Black] {
+            if strict && self.bb(color, Pawn).pop_count() > 8 {
+                return Err(FenError::TooManyPawns(color))
+            }
+            if strict && self.color_bb(color).pop_count() > 16 {
+                return Err(FenError::TooManyPieces(color))
+            }
+        }
+
+        for (side, right, king_square, rook_square) in CASTLING_SQUARES {
+            let color = if right.is_uppercase() { White } else { Black };
+            let possible = self.bb(color, King).get_bit(king_square) && self.bb(color, Rook).get_bit(rook_square);
+
+            if self.castling_ability.is_side_available(side) && !possible {
+                if strict {
+                    return Err(FenError::ImpossibleCastling(right))
+                }
+                self.castling_ability.byte &= !(side as u8);
+            }
+        }
+
+        if let Some(square) = self.enpassant_sq() {
+            if !self.is_enpassant_possible(square) {
+                if strict {
+                    return Err(FenError::ImpossibleEnPassant(Square::from(square)))
+                }
+                self.enpassant_square_bitboard = Bitboard::EMPTY;
+            }
+        }
+
+        // Move counters are kept as they are in lenient mode, so FENs of games going on past the 75 move rule round-trip
+        if strict && self.half_moves > MAX_HALF_MOVES {
+            return Err(FenError::HalfMovesTooLarge(self.half_moves))
+        }
+
+        Ok(())
+    }
+
+    /// Whether the opponent can just have moved a pawn two squares past the en passant square
+    fn is_enpassant_possible(&self, square: u8) -> bool {
+        // The pushed pawn is in front of the square seen from the side to move, and its start square is behind it
+        let (rank_ok, pawn_square, start_square) = match self.active_color {
+            White => ((16..24).contains(&square), square + 8, square.wrapping_sub(8)),
+            Black => ((40..48).contains(&square), square.wrapping_sub(8), square + 8),
+        };
+
+        rank_ok
+            && self.bb(self.active_color.opposite(), Pawn).get_bit(pawn_square)
+            && !self.all_occupancies.get_bit(square)
+            && !self.all_occupancies.get_bit(start_square)
+    }
+}
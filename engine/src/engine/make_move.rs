@@ -132,21 +132,24 @@ impl Position {
         self.castling_ability.update(src, dst);
         self.apply_castling_zobrist();
 
-        // Increment full moves
-        self.full_moves += color as u8;
+        // Increment full moves after black's move
+        if color.is_black() {
+            self.full_moves += 1;
+        }
 
         // Switch side
         self.active_color = opp_color;
         self.apply_side_zobrist();
 
         // Update half moves counter
+        // Positions before an irreversible move can not be repeated, but the position after it can
         if moove.is_capture() || piece == Pawn || moove.is_promotion() {
             self.half_moves = 0;
             self.rep_table.clear();
         }
         else {
-            self.half_moves += 1;
-            self.rep_table.push(self.zobrist_hash)
+            self.half_moves = self.half_moves.saturating_add(1);
         };
+        self.rep_table.push(self.zobrist_hash)
     }
 }
\ No newline at end of file
@@ -5,6 +5,8 @@ mod piece;
 mod color;
 mod bitboard;
 mod position;
+mod fen;
+mod san;
 mod castling;
 mod moove;
 mod make_move;
@@ -21,6 +23,7 @@ use moove::*;
 
 pub use castling::*;
 pub use position::*;
+pub use fen::*;
 pub use move_gen::*;
 pub use search::*;
 pub use settings::*;
\ No newline at end of file
@@ -7,6 +7,13 @@ use CastlingSide::*;
 use Square::*;
 use bitintr::{Pext, Pdep};
 
+/// Generate all legal moves
+pub const GEN_ALL: u8 = 0;
+/// Generate only captures, including enpassant and capturing promotions
+pub const GEN_CAPTURES: u8 = 1;
+/// Generate only non-captures, including castling and quiet promotions
+pub const GEN_QUIETS: u8 = 2;
+
 macro_rules! generate_pawn_captures {
     ($pos: expr, $move_list: expr, $has_enpassant_sq: expr, $src: expr, $check_mask: expr, $pin_mask: expr) => {
         match $has_enpassant_sq {
@@ -21,14 +28,51 @@ impl Position {
     #[inline(always)]
     pub fn generate_moves(&self) -> MoveList {
         if self.active_color.is_white() {
-            self.generate_moves_internal::<true>()
+            self.generate_moves_internal::<true, GEN_ALL>()
+        } else {
+            self.generate_moves_internal::<false, GEN_ALL>()
+        }
+    }
+
+    /// Generate all legal captures for the position
+    #[inline(always)]
+    pub fn generate_captures(&self) -> MoveList {
+        if self.active_color.is_white() {
+            self.generate_moves_internal::<true, GEN_CAPTURES>()
         } else {
-            self.generate_moves_internal::<false>()
+            self.generate_moves_internal::<false, GEN_CAPTURES>()
         }
     }
 
+    /// Generate all legal non-captures for the position
     #[inline(always)]
-    fn generate_moves_internal<const IS_WHITE: bool>(&self) -> MoveList {
+    pub fn generate_quiets(&self) -> MoveList {
+        if self.active_color.is_white() {
+            self.generate_moves_internal::<true, GEN_QUIETS>()
+        } else {
+            self.generate_moves_internal::<false, GEN_QUIETS>()
+        }
+    }
+
+    /// Generate all legal captures and quiet promotions to a queen, which are the moves searched in quiescence search
+    pub fn generate_noisy(&self) -> MoveList {
+        let mut move_list = self.generate_captures();
+
+        let color = self.active_color;
+        let promotion_rank = if color.is_white() { PAWN_INIT_BLACK_RANK } else { PAWN_INIT_WHITE_RANK };
+        for src in self.bb(color, Pawn) & promotion_rank {
+            let dst = if color.is_white() { src - 8 } else { src + 8 };
+            let promotion = Move::new_promotion(src, dst, Queen, false);
+            if self.is_legal(promotion) {
+                move_list.push(promotion);
+            }
+        }
+
+        move_list
+    }
+
+    #[inline(always)]
+    fn generate_moves_internal<const IS_WHITE: bool, const GEN: u8>(&self) -> MoveList {
         let color = if IS_WHITE { White } else { Black };
 
         let mut move_list = MoveList::new();
@@ -39,17 +83,21 @@ impl Position {
         let in_check = !(!check_mask).is_empty();
         let checkers = (check_mask & self.color_bb(color.opposite())).pop_count();
         if in_check && checkers > 1 {
-            self.generate_king_moves::<false>(&mut move_list);
+            self.generate_king_moves::<false, GEN>(&mut move_list);
             return move_list
         }
 
         // Generate the pin masks
         let hv_pin = self.generate_hv_pin_mask(color);
         let d12_pin = self.generate_d12_pin_mask(color);
-        let opp_or_empty = !self.color_bb(color);
+        let opp_or_empty = match GEN {
+            GEN_CAPTURES => self.color_bb(color.opposite()),
+            GEN_QUIETS => !self.all_occupancies,
+            _ => !self.color_bb(color),
+        };
         
         // Pawn moves
-        self.generate_pawn_moves(&mut move_list, check_mask, hv_pin, d12_pin);
+        self.generate_pawn_moves::<GEN>(&mut move_list, check_mask, hv_pin, d12_pin);
 
         // Knight moves. Only unpinned can move
         let unpinned_knights = self.bb(color, Knight) & !(hv_pin | d12_pin);
@@ -115,7 +163,7 @@ impl Position {
         }
 
         // King moves
-        self.generate_king_moves::<true>(&mut move_list);
+        self.generate_king_moves::<true, GEN>(&mut move_list);
 
         move_list
     }
@@ -222,29 +270,37 @@ impl Position {
     }
 
     #[inline(always)]
-    fn generate_pawn_moves(&self, move_list: &mut MoveList, check_mask: Bitboard, hv_pin: Bitboard, d12_pin: Bitboard) {
+    fn generate_pawn_moves<const GEN: u8>(&self, move_list: &mut MoveList, check_mask: Bitboard, hv_pin: Bitboard, d12_pin: Bitboard) {
         let pawns = self.bb(self.active_color, Pawn);
         let has_enpassant = !self.enpassant_square_bitboard.is_empty();
 
-        let hv_pinned_pawns = pawns & hv_pin;
-        for src in hv_pinned_pawns {
-            self.generate_quiet_pawn_moves(move_list, src, check_mask & hv_pin)
+        if GEN != GEN_CAPTURES {
+            let hv_pinned_pawns = pawns & hv_pin;
+            for src in hv_pinned_pawns {
+                self.generate_quiet_pawn_moves(move_list, src, check_mask & hv_pin)
+            }
         }
 
-        let d12_pinned_pawns = pawns & d12_pin;
-        for src in d12_pinned_pawns {
-            generate_pawn_captures!(self, move_list, has_enpassant, src, check_mask, d12_pin);
+        if GEN != GEN_QUIETS {
+            let d12_pinned_pawns = pawns & d12_pin;
+            for src in d12_pinned_pawns {
+                generate_pawn_captures!(self, move_list, has_enpassant, src, check_mask, d12_pin);
+            }
         }
 
         let unpinned_pawns = pawns & !(hv_pin | d12_pin);
         for src in unpinned_pawns {
-            self.generate_quiet_pawn_moves(move_list, src, check_mask);
-            generate_pawn_captures!(self, move_list, has_enpassant, src, check_mask, Bitboard::FULL);
+            if GEN != GEN_CAPTURES {
+                self.generate_quiet_pawn_moves(move_list, src, check_mask);
+            }
+            if GEN != GEN_QUIETS {
+                generate_pawn_captures!(self, move_list, has_enpassant, src, check_mask, Bitboard::FULL);
+            }
         }
     }
 
     #[inline(always)]
-    fn generate_king_moves<const GEN_CASTLING: bool>(&self, move_list: &mut MoveList) {
+    fn generate_king_moves<const GEN_CASTLING: bool, const GEN: u8>(&self, move_list: &mut MoveList) {
         let color = self.active_color;
 
         let attacked = 
@@ -256,13 +312,17 @@ impl Position {
             self.get_attacked_wo_king(color, King);
 
         let king_pos = self.king_position(color);
-        let opp_or_empty = !self.color_bb(color);
+        let opp_or_empty = match GEN {
+            GEN_CAPTURES => self.color_bb(color.opposite()),
+            GEN_QUIETS => !self.all_occupancies,
+            _ => !self.color_bb(color),
+        };
 
         let legal = king_attacks(king_pos) & !attacked & opp_or_empty;
 
         self.add_normal_moves(move_list, king_pos, legal);
 
-        if !GEN_CASTLING || !(attacked & self.bb(color, King)).is_empty() {
+        if !GEN_CASTLING || GEN == GEN_CAPTURES || !(attacked & self.bb(color, King)).is_empty() {
             return
         }
 
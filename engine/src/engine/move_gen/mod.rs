@@ -2,9 +2,10 @@ mod attack_tables;
 mod generate_moves;
 mod move_list;
 mod pin_and_check_masks;
+mod move_validation;
 
 pub use attack_tables::*;
-use move_list::*;
+pub use move_list::*;
 use pin_and_check_masks::*;
 
 pub use crate::engine::*;
\ No newline at end of file
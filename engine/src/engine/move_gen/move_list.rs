@@ -1,7 +1,11 @@
 use super::*;
 
+/// Legal positions have at most 218 moves. Lenient FENs with more pieces than a game can have may have more,
+/// and the moves past this are dropped
+pub const MAX_MOVES: usize = 256;
+
 pub struct MoveList {
-    moves: heapless::Vec<(Move, u16), 256>
+    moves: heapless::Vec<(Move, i32), MAX_MOVES>
 }
 
 impl MoveList {
@@ -12,7 +16,7 @@ impl MoveList {
     }
 
     pub fn push(&mut self, moov: Move) {
-        unsafe { self.moves.push_unchecked((moov, 0)) }
+        let _ = self.moves.push((moov, 0));
     }
 
     pub fn pop(&mut self) -> Option<Move> {
@@ -28,7 +32,7 @@ impl MoveList {
         let mut best_index = 0;
         let mut best_score = self.moves[0].1;
 
-        for i in 1..self.len() - 1 {
+        for i in 1..self.len() {
             let score = self.moves[i].1;
 
             if score > best_score {
@@ -44,12 +48,31 @@ impl MoveList {
         self.moves.len()
     }
 
+    /// Removes the move from the list. Returns false if it was not in the list
+    pub fn remove(&mut self, moov: Move) -> bool {
+        match self.moves.iter().position(|(m, _)| *m == moov) {
+            Some(index) => {
+                self.moves.swap_remove(index);
+                true
+            },
+            None => false
+        }
+    }
+
     pub fn sort(mut self, pos: &Position, context: &mut SearchContext, tt_move: Move, ply: u8) -> Self {
         for i in 0..self.len() {
             self.moves[i].1 = self.moves[i].0.score_move(pos, context, tt_move, ply)
         }
         self
     }
+
+    /// Sorts by MVV-LVA only, which is used in quiescence search
+    pub fn sort_noisy(mut self, pos: &Position, tt_move: Move) -> Self {
+        for i in 0..self.len() {
+            self.moves[i].1 = self.moves[i].0.score_noisy(pos, tt_move)
+        }
+        self
+    }
 }
 
 impl Iterator for MoveList {
@@ -0,0 +1,154 @@
+use super::*;
+
+use PieceType::*;
+use CastlingSide::*;
+use Square::*;
+
+impl Position {
+    /// Determines whether an arbitrary move could have been generated in this position, ignoring pins and checks.
+    ///
+    /// Castling is fully validated, as the generator only produces legal castling moves.
+    /// This allows moves from the transposition table or killer moves to be tried without generating all moves.
+    pub fn is_pseudo_legal(&self, moove: Move) -> bool {
+        if moove.is_null() {
+            return false
+        }
+
+        let color = self.active_color;
+        let opp_color = color.opposite();
+        let src = moove.src();
+        let dst = moove.dst();
+
+        if !self.color_bb(color).get_bit(src) || self.color_bb(color).get_bit(dst) {
+            return false
+        }
+
+        let piece = self.piece_type_at(src);
+
+        // Castling moves
+        if moove.is_castle_ks() || moove.is_castle_qs() {
+            if piece != King {
+                return false
+            }
+
+            let (side, from, to) = match (color, moove.is_castle_ks()) {
+                (Color::White, true)  => (WhiteKingSide,  e1, g1),
+                (Color::White, false) => (WhiteQueenSide, e1, c1),
+                (Color::Black, true)  => (BlackKingSide,  e8, g8),
+                (Color::Black, false) => (BlackQueenSide, e8, c8),
+            };
+
+            if src != from as u8 || dst != to as u8 || !self.castling_ability.is_side_available(side) {
+                return false
+            }
+
+            if !(self.all_occupancies & side.open_mask()).is_empty() || self.is_in_check() {
+                return false
+            }
+
+            // Like the generator, the squares are checked as if the king was not on the board
+            let occ_wo_king = (self.all_occupancies ^ self.bb(color, King)).as_u64();
+            return !Bitboard(side.attacked_mask()).any(|sq| self.is_square_attacked_with_occ(sq, opp_color, occ_wo_king, 0))
+        }
+
+        // Captures must capture an enemy piece, other moves must go to an empty square
+        if moove.is_enpassant() {
+            if piece != Pawn || !self.enpassant_square_bitboard.get_bit(dst) {
+                return false
+            }
+            return pawn_attacks(src, color).get_bit(dst)
+        } else if moove.is_capture() != self.color_bb(opp_color).get_bit(dst) {
+            return false
+        }
+
+        // Remaining invalid move type bits are castling with the capture flag
+        if !moove.is_promotion() && moove.data & 0b1000 != 0 {
+            return false
+        }
+
+        if piece == Pawn {
+            // Pawns must promote on the last rank, and only there
+            if moove.is_promotion() != END_RANKS.get_bit(dst) {
+                return false
+            }
+
+            let forward = |sq: u8| if color.is_white() { sq.checked_sub(8) } else { Some(sq + 8).filter(|sq| *sq < 64) };
+
+            if moove.is_capture() {
+                return pawn_attacks(src, color).get_bit(dst)
+            }
+
+            if moove.is_double_push() {
+                let init_rank = if color.is_white() { PAWN_INIT_WHITE_RANK } else { PAWN_INIT_BLACK_RANK };
+                let between = match forward(src) {
+                    Some(sq) => sq,
+                    None => return false,
+                };
+                return init_rank.get_bit(src) && forward(between) == Some(dst) && !self.all_occupancies.get_bit(between)
+            }
+
+            return forward(src) == Some(dst)
+        }
+
+        // Only pawns can make these moves
+        if moove.is_promotion() || moove.is_double_push() {
+            return false
+        }
+
+        let attacks = match piece {
+            Knight => knight_attacks(src),
+            Bishop => d12_attacks(src, self.all_occupancies),
+            Rook =>   hv_attacks(src, self.all_occupancies),
+            Queen =>  d12_attacks(src, self.all_occupancies) | hv_attacks(src, self.all_occupancies),
+            King =>   king_attacks(src),
+            _ => return false,
+        };
+
+        attacks.get_bit(dst)
+    }
+
+    /// Determines whether an arbitrary move is legal in this position
+    pub fn is_legal(&self, moove: Move) -> bool {
+        if !self.is_pseudo_legal(moove) {
+            return false
+        }
+
+        if moove.is_castle_ks() || moove.is_castle_qs() {
+            return true
+        }
+
+        // Check whether the king is attacked in the occupancies after the move, without making it
+        let color = self.active_color;
+        let src = moove.src();
+        let dst = moove.dst();
+        let king_sq = if self.piece_type_at(src) == King { dst } else { self.king_position(color) };
+
+        let mut occ = self.all_occupancies.as_u64() ^ (1 << src) | (1 << dst);
+        let mut captured = 1 << dst;
+        if moove.is_enpassant() {
+            let captured_sq = if color.is_white() { dst + 8 } else { dst - 8 };
+            occ ^= 1 << captured_sq;
+            captured = 1 << captured_sq;
+        }
+
+        !self.is_square_attacked_with_occ(king_sq, color.opposite(), occ, captured)
+    }
+
+    /// Determines whether a square is attacked by any piece of the given color
+    pub fn is_square_attacked(&self, square: u8, attacker: Color) -> bool {
+        self.is_square_attacked_with_occ(square, attacker, self.all_occupancies.as_u64(), 0)
+    }
+
+    /// Determines whether a square is attacked with the given occupancies, ignoring attackers on the captured squares
+    fn is_square_attacked_with_occ(&self, square: u8, attacker: Color, occ: u64, captured: u64) -> bool {
+        let attackers = |piece: PieceType| self.bb(attacker, piece).as_u64() & !captured;
+        let occ = Bitboard(occ);
+
+        // A pawn of the defending color on the square attacks the same squares as the attacking pawns it is attacked by
+        pawn_attacks(square, attacker.opposite()) & attackers(Pawn) != 0 ||
+        knight_attacks(square) & attackers(Knight) != 0 ||
+        king_attacks(square) & attackers(King) != 0 ||
+        d12_attacks(square, occ) & (attackers(Bishop) | attackers(Queen)) != 0 ||
+        hv_attacks(square, occ) & (attackers(Rook) | attackers(Queen)) != 0
+    }
+}
@@ -19,8 +19,8 @@ pub struct Position {
     pub enpassant_square_bitboard: Bitboard,
     pub castling_ability: CastlingAbility,
 
-    pub full_moves: u8,
-    pub half_moves: u8,
+    pub full_moves: u16,
+    pub half_moves: u16,
     pub zobrist_hash: u64,
 
     pub rep_table: RepetitionTable,
@@ -31,86 +31,6 @@ impl Position {
         Position::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").unwrap()
     }
 
-    pub fn from_fen(input: &str) -> Result<Self, String> {
-        let mut split = input.trim().split(' ');
-        
-        // Get and remember board pieces string
-        let board_str = match split.next() {
-            Some(str) => str,
-            None => return Err(format!("Expected board string in fen")),
-        };
-
-        // Active color
-        let active_color = if let Some(color_str) = split.next() {
-            Color::from_str(color_str)?
-        } else {
-            return Err(format!("Expected a color char"))
-        };
-
-        // Castling ability
-        let castling_ability = CastlingAbility::from_str(split.next().unwrap_or("-"))?;
-
-        // Enpassant square
-        let mut enpassant_square = Bitboard::EMPTY;
-        if let Some(enp_str) = split.next() {
-            if enp_str != "-" {
-                enpassant_square.set_bit(Square::from_str(enp_str)? as u8)
-            }
-        };
-
-        // 50 move rule count
-        let half_moves: u8 = if let Some(hm_str) = split.next() {
-            match hm_str.parse() {
-                Ok(i) => i,
-                Err(_) => return Err(format!("Half moves was not a number")),
-            }
-        } else { 0 };
-
-        // Full moves
-        let full_moves: u8 = if let Some(fm_str) = split.next() {
-            match fm_str.parse() {
-                Ok(i) => i,
-                Err(_) => return Err(format!("Full moves was not a number")),
-            }
-        } else { 0 };
-
-        // Init position with empty board
-        let mut pos = Position { 
-            bitboards: [Bitboard::EMPTY; 12],
-            color_occupancies: [Bitboard::EMPTY; 2],
-            all_occupancies: Bitboard::EMPTY,
-            piece_squares: [Empty; 64],
-            active_color,
-            enpassant_square_bitboard: enpassant_square,
-            castling_ability,
-            full_moves,
-            half_moves,
-            zobrist_hash: 0,
-            rep_table: RepetitionTable::new(),
-        };
-
-        // Place pieces
-        let mut square = 0;
-        for char in board_str.chars() {
-            if square > 63 {
-                return Err(format!("Board from fen does not fit on the board"))
-            }
-            
-            if let Some(i) = char.to_digit(10) {
-                square += i as u8
-            } else if char != '/' {
-                let (color, piece_type) = char_to_piece(char)?;
-                pos.place_piece(color, piece_type, square);
-                square += 1;
-            }
-        }
-        
-        // Initialize zobrist
-        pos.generate_zobrist_hash();
-
-        Ok(pos)
-    }
-
     pub fn enpassant_sq(&self) -> Option<u8> {
         if self.enpassant_square_bitboard.is_empty() {
             None
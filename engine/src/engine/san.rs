@@ -0,0 +1,66 @@
+use super::*;
+
+use PieceType::*;
+
+impl Position {
+    /// The move in standard algebraic notation, without check and mate suffixes. The move must be legal
+    pub fn san(&self, moove: Move) -> String {
+        if moove.is_castle_ks() {
+            return "O-O".to_string()
+        } else if moove.is_castle_qs() {
+            return "O-O-O".to_string()
+        }
+
+        let piece = self.piece_type_at(moove.src());
+        let src = Square::from(moove.src()).to_string();
+        let dst = Square::from(moove.dst()).to_string();
+        let capture = if moove.is_capture() { "x" } else { "" };
+
+        if piece == Pawn {
+            let file = if moove.is_capture() { &src[0..1] } else { "" };
+            let promotion = if moove.is_promotion() { format!("={}", piece_char(Color::White, moove.promotion())) } else { String::new() };
+            return format!("{file}{capture}{dst}{promotion}")
+        }
+
+        // Other pieces of the same type that can move to the same square
+        let others: Vec<u8> = self.generate_moves()
+            .filter(|m| m.dst() == moove.dst() && m.src() != moove.src() && self.piece_type_at(m.src()) == piece)
+            .map(|m| m.src())
+            .collect();
+
+        let disambiguation = if others.is_empty() {
+            ""
+        } else if others.iter().all(|&sq| sq % 8 != moove.src() % 8) {
+            &src[0..1]
+        } else if others.iter().all(|&sq| sq / 8 != moove.src() / 8) {
+            &src[1..2]
+        } else {
+            &src
+        };
+
+        format!("{}{disambiguation}{capture}{dst}", piece_char(Color::White, piece))
+    }
+
+    /// Finds the legal move given in standard algebraic notation.
+    /// Check, mate and annotation suffixes are ignored, and moves with more disambiguation than needed are accepted
+    pub fn parse_san(&self, san: &str) -> Result<Move, String> {
+        let normalized = san.trim_end_matches(['+', '#', '!', '?']).replace('0', "O").replace('=', "");
+
+        self.generate_moves().find(|&m| {
+            let own = self.san(m).replace('=', "");
+            if normalized == own {
+                return true
+            }
+
+            // Also accept the piece moves disambiguated by file, rank or both when it is not needed
+            let piece = self.piece_type_at(m.src());
+            if piece == Pawn || m.is_castle_ks() || m.is_castle_qs() {
+                return false
+            }
+            let src = Square::from(m.src()).to_string();
+            let tail = format!("{}{}", if m.is_capture() { "x" } else { "" }, Square::from(m.dst()));
+            let letter = piece_char(Color::White, piece);
+            [&src[0..1], &src[1..2], &src[..]].iter().any(|d| normalized == format!("{letter}{d}{tail}"))
+        }).ok_or(format!("Illegal move: {san}"))
+    }
+}
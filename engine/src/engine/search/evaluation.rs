@@ -1,4 +1,4 @@
-use std::ops::{Add, Sub};
+use std::{ops::{Add, Sub}, fmt::{self, Display, Formatter}};
 
 use super::*;
 use Color::*;
@@ -9,6 +9,25 @@ pub type WeightArray = [i16; WEIGHT_COUNT];
 
 pub const WEIGHTS: WeightArray = EVAL_CONSTS;
 pub const WEIGHT_COUNT: usize = 798;
+
+// Offsets of the individual terms in the WeightArray. Must match the layout of EVAL_CONSTS
+pub const MATERIAL_EARLY_OFFSET: usize = 0;
+pub const MATERIAL_LATE_OFFSET: usize = 4;
+/// Piece square table offsets, indexed by piece type
+pub const PST_EARLY_OFFSETS: [usize; 6] = [8, 136, 264, 392, 520, 648];
+/// Piece square table offsets, indexed by piece type
+pub const PST_LATE_OFFSETS: [usize; 6] = [72, 200, 328, 456, 584, 712];
+pub const STACKED_PAWN_OFFSET: usize = 776;
+pub const ISOLATED_PAWN_OFFSET: usize = 777;
+pub const PASSED_PAWN_OFFSET: usize = 778;
+pub const SEMI_OPEN_FILE_OFFSET: usize = 786;
+pub const OPEN_FILE_OFFSET: usize = 787;
+pub const PROTECTED_KING_EARLY_OFFSET: usize = 788;
+/// Mobility offsets for knight, bishop, rook and queen
+pub const MOBILITY_EARLY_OFFSET: usize = 789;
+pub const PROTECTED_KING_LATE_OFFSET: usize = 793;
+/// Mobility offsets for knight, bishop, rook and queen
+pub const MOBILITY_LATE_OFFSET: usize = 794;
 //pub const WEIGHTS: [i16; 798] = [332, 348, 533, 1005, 330, 334, 508, 971, 18, 2, 7, -7, 6, -4, -5, -2, 343, 100, 162, 9, -34, 6, 199, -7, 57, 74, 66, -7, 64, 25, 90, 40, 15, 33, -10, 18, 2, 68, 16, -8, -49, 24, -33, -11, -6, 15, 35, -44, -29, -22, 0, 11, 22, 28, 20, 23, -37, 22, -51, -42, 3, 32, 36, -14, 78, 21, -1, 11, -8, 9, 3, 5, 6, -6, 0, 4, 4, 1, 5, -6, -25, 107, 36, 44, 99, 63, 17, 96, 65, 37, 66, 9, 6, 7, 19, 36, 35, 38, 19, 3, -5, -7, -6, 16, 46, 2, 8, -23, -16, -6, -16, 19, 22, 30, -21, -14, 13, -6, -6, 1, 32, 14, 26, 5, 28, 1, -8, 7, 79, 41, 24, 16, -3, 4, 10, -4, -78, -65, 1, -34, -19, -36, -47, -87, -79, 29, 76, 76, 51, 48, 12, -40, -15, 98, 82, 51, 106, 83, 47, -22, -17, 4, 48, 83, 54, 21, 3, 48, -1, -10, 11, 0, 53, 57, 52, 16, -15, 37, 10, 22, 48, 8, 6, 63, -82, -103, -37, -9, -32, 35, -77, -1, -14, 12, -45, -22, -58, 35, -19, -225, 33, -43, 18, -32, -51, -22, -83, -68, -11, 40, -2, 12, -28, -14, -46, -81, -28, -4, 11, 13, -1, -28, -21, -46, -58, 27, 10, 24, 24, -21, 2, -52, -12, -8, 6, 19, 15, 16, -19, -28, -42, -56, 0, -3, -2, -26, -27, -88, -54, -23, -30, -29, 19, -2, 12, -45, -135, -20, -62, -42, -24, -69, -55, -11, 61, 74, -35, 12, -63, -12, -10, 21, -40, -4, -31, 27, -22, 78, 15, -3, 31, 11, 9, 21, -10, 57, 112, 47, 12, 38, 18, 32, 13, 55, -11, -26, -12, 20, -16, 6, 50, 15, 29, -21, -3, -20, 25, 8, 10, 13, 26, -1, 99, -23, 120, 24, -6, 30, 38, -31, -64, -41, -4, -15, 8, -37, -110, -149, 1, -17, -7, -2, -10, -13, -23, -21, 14, 20, 16, -17, 18, -11, 17, -67, -22, 12, -6, 9, 24, 13, 0, 5, 10, 40, 14, 24, 23, -20, 36, -5, -11, 28, -11, 39, -10, 18, -22, -27, 0, 8, 15, -4, 21, 3, -37, 13, -36, -35, -54, 17, -12, -20, 0, -55, -43, -27, -26, -10, -13, -55, -27, -10, 28, 83, 44, 61, 25, 58, 119, 98, 51, 23, 63, 120, 129, 96, 123, 68, -51, 44, 56, 41, 61, 58, 105, 87, -6, -26, 14, 48, 23, 2, 20, 21, 9, -121, -32, 13, 22, 5, 56, -13, -26, -53, -24, -22, -23, -31, -5, -9, 16, -12, 16, -12, 3, 21, -48, -5, -9, 3, 6, 11, -4, -11, -71, -21, 6, 0, 16, 12, 16, 31, 22, -1, 26, 22, 17, -13, -19, 7, -8, -1, 43, 12, 11, 8, -7, 6, -4, 6, 24, 18, 20, 5, -6, 19, 15, 14, 4, 44, 22, 2, -10, 9, -11, 3, 11, 9, 9, 7, -14, -10, -15, -24, -21, -27, -22, 5, -21, -20, -29, -30, 6, -24, -16, 2, -19, -27, -18, -44, 6, -7, 57, 11, 86, 102, 182, 39, 22, -22, 5, -4, 10, 33, -90, 63, -10, -26, 16, 9, 19, 32, 65, 52, 12, -19, 5, -17, 67, 35, -9, -39, 3, 0, -1, -41, -11, -7, -6, -26, 18, -20, -7, 4, -7, 21, -2, 2, 6, -13, -10, 47, 32, 15, -22, -43, 28, -4, -38, -4, 31, -54, 3, -127, 3, 40, 25, 43, -9, 6, -66, -1, 27, 65, 61, 34, 32, 18, 35, 10, 17, 36, 20, 17, 49, 2, 16, 18, -4, 41, 48, 41, 13, 46, 51, 4, -8, 47, 39, 43, 16, 54, 43, 25, 26, 4, 28, 16, 18, -13, -1, -24, 22, -18, -14, -60, -29, -28, -41, -96, -14, 12, -31, -58, -54, -44, 8, -56, -35, 117, 67, 201, 112, 225, 167, -9, 112, 147, 11, 0, 49, -127, 11, -72, 50, 68, -27, -57, -71, -150, -251, -91, 24, 25, -17, -103, -84, -86, -27, -58, 59, -73, -24, -12, -38, 19, -45, -57, -39, 28, 16, -33, 41, 2, -30, -51, 105, 16, 34, -108, -5, 0, -10, 21, -129, 53, 36, -95, -4, -53, 3, 29, -72, 15, 14, 42, 21, -2, 39, -51, 25, 11, 49, 38, 43, 39, 61, 20, 16, 34, 47, 62, 50, 36, 22, 33, 18, 23, 41, 48, 43, 32, 35, 15, -23, 58, 24, 24, 22, 21, 22, 6, 6, -11, 2, 15, 16, 10, 13, -2, -33, 9, 1, 26, 18, -5, -10, -18, 62, -48, 0, -2, -46, -21, -40, -59, 9, 13, 15, 15, 34, 56, 76, 120, 233, 228, 6, 7, 3, -3, 4, -2, 1, 3, -4, -1, 2, -2];
 
 /// Mirror positional score tables for opposite side
@@ -93,16 +112,54 @@ impl Position {
         (self.bb(White, piece).pop_count() + self.bb(Black, piece).pop_count()) as i16
     }
 
+    /// The game phase, scaled from 0 in the early game to 256 in the late game
     #[inline(always)]
-    pub fn evaluate(&self, evaluator: impl Evaluator) -> i16 {
+    pub fn phase(&self) -> f32 {
+        let p = TOTAL_PHASE - self.count_pieces(Pawn) * PAWN_PHASE
+            - self.count_pieces(Knight) * KNIGHT_PHASE
+            - self.count_pieces(Bishop) * BISHOP_PHASE
+            - self.count_pieces(Rook) * ROOK_PHASE
+            - self.count_pieces(Queen) * QUEEN_PHASE;
+
+        ((p as f32) * 256. + ((TOTAL_PHASE as f32) / 2.)) / (TOTAL_PHASE as f32)
+    }
+
+    #[inline(always)]
+    pub fn evaluate(&self, evaluator: &impl Evaluator) -> i16 {
+        self.evaluate_traced::<false>(evaluator, &mut EvalTrace::default())
+    }
+
+    /// Evaluates the position, and breaks the evaluation down into its terms
+    pub fn trace(&self, evaluator: &impl Evaluator) -> EvalTrace {
+        let mut trace = EvalTrace::default();
+        self.evaluate_traced::<true>(evaluator, &mut trace);
+        trace
+    }
+
+    /// Evaluates the position. If TRACE is set, the individual terms are recorded in the trace
+    #[inline(always)]
+    fn evaluate_traced<const TRACE: bool>(&self, evaluator: &impl Evaluator, trace: &mut EvalTrace) -> i16 {
         let mut score: ScorePair = ScorePair::ZERO;
 
+        macro_rules! trace {
+            ($term: expr, $color: expr, $early: expr, $late: expr) => {
+                if TRACE {
+                    let (early, late) = &mut trace.terms[$term][$color as usize];
+                    *early += $early;
+                    *late += $late;
+                }
+            };
+        }
+
         for bb in 0..12 {
             for square in self.bitboards[bb].map(|sq| sq as usize) {
                 score = score + ScorePair::from((evaluator.material_weights_early()[bb], evaluator.material_weights_late()[bb]));
 
                 let (color, piece) = index_to_piece(bb);
 
+                // Material weights are signed by color, while the trace is from the perspective of each side
+                trace!(MATERIAL_TERM, color, evaluator.material_weights_early()[piece.index(White)], evaluator.material_weights_late()[piece.index(White)]);
+
                 let piece_square_index = match color {
                     White => square,
                     Black => MIRROR[square]
@@ -129,6 +186,11 @@ impl Position {
 
                         let score = -stack_penalty - isolated_penalty + passed_pawn_bonus;// + supporting_pawn_bonus
 
+                        trace!(STACKED_PAWNS_TERM, color, -stack_penalty, -stack_penalty);
+                        trace!(ISOLATED_PAWNS_TERM, color, -isolated_penalty, -isolated_penalty);
+                        trace!(PASSED_PAWNS_TERM, color, passed_pawn_bonus, passed_pawn_bonus);
+                        trace!(PST_TERM, color, evaluator.pawn_scores_early()[piece_square_index], evaluator.pawn_scores_late()[piece_square_index]);
+
                         (score + evaluator.pawn_scores_early()[piece_square_index], score + evaluator.pawn_scores_late()[piece_square_index])
                     },
                     Knight => {
@@ -139,6 +201,9 @@ impl Position {
                             evaluator.knight_mobility_bonus_late() * move_cnt
                         );
 
+                        trace!(MOBILITY_TERM, color, early_mob, late_mob);
+                        trace!(PST_TERM, color, evaluator.knight_scores_early()[piece_square_index], evaluator.knight_scores_late()[piece_square_index]);
+
                         (
                             evaluator.knight_scores_early()[piece_square_index] + early_mob,
                             evaluator.knight_scores_late()[piece_square_index] + late_mob
@@ -152,6 +217,9 @@ impl Position {
                             evaluator.bishop_mobility_bonus_late() * move_cnt
                         );
 
+                        trace!(MOBILITY_TERM + 1, color, early_mob, late_mob);
+                        trace!(PST_TERM, color, evaluator.bishop_scores_early()[piece_square_index], evaluator.bishop_scores_late()[piece_square_index]);
+
                         (
                             evaluator.bishop_scores_early()[piece_square_index] + early_mob,
                             evaluator.bishop_scores_late()[piece_square_index] + late_mob
@@ -173,6 +241,10 @@ impl Position {
                         
                         let score = semi_open_bonus + open_bonus;
 
+                        trace!(ROOK_FILES_TERM, color, score, score);
+                        trace!(MOBILITY_TERM + 2, color, early_mob, late_mob);
+                        trace!(PST_TERM, color, evaluator.rook_scores_early()[piece_square_index], evaluator.rook_scores_late()[piece_square_index]);
+
                         (
                             score + evaluator.rook_scores_early()[piece_square_index] + early_mob,
                             score + evaluator.rook_scores_late()[piece_square_index] + late_mob
@@ -186,6 +258,9 @@ impl Position {
                             evaluator.queen_mobility_bonus_late() * move_cnt
                         );
 
+                        trace!(MOBILITY_TERM + 3, color, early_mob, late_mob);
+                        trace!(PST_TERM, color, evaluator.queen_scores_early()[piece_square_index], evaluator.queen_scores_late()[piece_square_index]);
+
                         (
                             evaluator.queen_scores_early()[piece_square_index] + early_mob,
                             evaluator.queen_scores_late()[piece_square_index] + late_mob
@@ -207,6 +282,10 @@ impl Position {
 
                         let score = -semi_open_penalty - open_penalty;
 
+                        trace!(KING_FILES_TERM, color, score, score);
+                        trace!(KING_PROTECTION_TERM, color, early_safety, late_safety);
+                        trace!(PST_TERM, color, evaluator.king_scores_early()[piece_square_index], evaluator.king_scores_late()[piece_square_index]);
+
                         (
                             score + evaluator.king_scores_early()[piece_square_index] + early_safety,
                             score + evaluator.king_scores_late()[piece_square_index] + late_safety
@@ -222,22 +301,204 @@ impl Position {
             }
         }
 
-        let phase: f32 = {
-            let p = TOTAL_PHASE - self.count_pieces(Pawn) * PAWN_PHASE
-            - self.count_pieces(Knight) * KNIGHT_PHASE
-            - self.count_pieces(Bishop) * BISHOP_PHASE
-            - self.count_pieces(Rook) * ROOK_PHASE
-            - self.count_pieces(Queen) * QUEEN_PHASE;
-
-            ((p as f32) * 256. + ((TOTAL_PHASE as f32) / 2.)) / (TOTAL_PHASE as f32)
-        };
+        let phase = self.phase();
 
         let eval = (((score.early() as f32 * (256. - phase)) + (score.late() as f32 * phase)) / 256.) as i16;
 
+        if TRACE {
+            trace.phase = phase;
+            trace.eval = eval;
+        }
+
         if self.active_color.is_white() { eval } else { -eval }
     }
 }
 
+/// Names of the terms in an evaluation trace
+pub const TRACE_TERMS: [&str; 12] = [
+    "Material",
+    "Piece squares",
+    "Knight mobility",
+    "Bishop mobility",
+    "Rook mobility",
+    "Queen mobility",
+    "Stacked pawns",
+    "Isolated pawns",
+    "Passed pawns",
+    "Rook files",
+    "King files",
+    "King protection",
+];
+
+const MATERIAL_TERM: usize = 0;
+const PST_TERM: usize = 1;
+/// Mobility terms for knight, bishop, rook and queen
+const MOBILITY_TERM: usize = 2;
+const STACKED_PAWNS_TERM: usize = 6;
+const ISOLATED_PAWNS_TERM: usize = 7;
+const PASSED_PAWNS_TERM: usize = 8;
+const ROOK_FILES_TERM: usize = 9;
+const KING_FILES_TERM: usize = 10;
+const KING_PROTECTION_TERM: usize = 11;
+
+/// The evaluation of a position broken down into its terms
+#[derive(Default)]
+pub struct EvalTrace {
+    /// Early and late score of each term, indexed by term and color. Each from the perspective of that color
+    pub terms: [[(i16, i16); 2]; TRACE_TERMS.len()],
+    pub phase: f32,
+    /// The final blended evaluation from white's perspective
+    pub eval: i16,
+}
+
+impl EvalTrace {
+    /// Early and late total of a term from white's perspective
+    pub fn total(&self, term: usize) -> (i16, i16) {
+        let [(white_early, white_late), (black_early, black_late)] = self.terms[term];
+        (white_early - black_early, white_late - black_late)
+    }
+}
+
+impl Display for EvalTrace {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let pawns = |score: i16| format!("{:6.2}", score as f32 / 100.);
+        let separator = "-----------------+---------------+---------------+---------------";
+
+        writeln!(f, "      Term       |     White     |     Black     |     Total     ")?;
+        writeln!(f, "                 |  Early   Late |  Early   Late |  Early   Late ")?;
+        writeln!(f, "{separator}")?;
+
+        let (mut total_early, mut total_late) = (0, 0);
+        for (term, name) in TRACE_TERMS.iter().enumerate() {
+            let [(white_early, white_late), (black_early, black_late)] = self.terms[term];
+            let (early, late) = self.total(term);
+            total_early += early;
+            total_late += late;
+            writeln!(f, "{name:>16} | {} {} | {} {} | {} {} ", pawns(white_early), pawns(white_late), pawns(black_early), pawns(black_late), pawns(early), pawns(late))?;
+        }
+
+        writeln!(f, "{separator}")?;
+        writeln!(f, "{:>16} |               |               | {} {} ", "Total", pawns(total_early), pawns(total_late))?;
+        writeln!(f)?;
+        writeln!(f, "Phase: {:.1} / 256 (0 is early game, 256 is late game)", self.phase)?;
+        write!(f, "Final evaluation: {} (white side)", pawns(self.eval).trim())
+    }
+}
+
+/// A term of the evaluation for one side, which is linear in a single weight of the WeightArray
+#[derive(Clone, Copy)]
+pub struct EvalFeature {
+    pub color: Color,
+    pub index: u16,
+    /// Multiplier of the weight in the early game score
+    pub early: i16,
+    /// Multiplier of the weight in the late game score
+    pub late: i16,
+}
+
+/// The evaluation of a position broken down into the features the weights are multiplied with
+pub struct EvalFeatures {
+    pub features: Vec<EvalFeature>,
+    /// Early and late score not covered by the weights (Pawn material), from white's perspective
+    pub constant: (i16, i16),
+    pub phase: f32,
+}
+
+impl Position {
+    /// Extracts the features used by evaluate, so the evaluation can be calculated as a weighted sum.
+    /// 
+    /// Must be kept in sync with evaluate
+    pub fn eval_features(&self) -> EvalFeatures {
+        let mut features = Vec::new();
+
+        let mut add = |color: Color, index: usize, early: i16, late: i16| {
+            if early != 0 || late != 0 {
+                features.push(EvalFeature { color, index: index as u16, early, late })
+            }
+        };
+
+        for bb in 0..12 {
+            for square in self.bitboards[bb].map(|sq| sq as usize) {
+                let (color, piece) = index_to_piece(bb);
+
+                // Material. Pawns and kings are not tuned
+                if piece != Pawn && piece != King {
+                    add(color, MATERIAL_EARLY_OFFSET + piece as usize - 1, 1, 0);
+                    add(color, MATERIAL_LATE_OFFSET + piece as usize - 1, 0, 1);
+                }
+
+                let piece_square_index = match color {
+                    White => square,
+                    Black => MIRROR[square]
+                };
+
+                add(color, PST_EARLY_OFFSETS[piece as usize] + piece_square_index, 1, 0);
+                add(color, PST_LATE_OFFSETS[piece as usize] + piece_square_index, 0, 1);
+
+                match piece {
+                    Pawn => {
+                        let stacked_pawns = (self.bb(color, Pawn) & FILE_MASKS[square]).pop_count() as i16;
+                        add(color, STACKED_PAWN_OFFSET, 1 - stacked_pawns, 1 - stacked_pawns);
+
+                        if (self.bb(color, Pawn) & ISOLATED_MASKS[square]).is_empty() {
+                            add(color, ISOLATED_PAWN_OFFSET, -1, -1);
+                        }
+
+                        let (is_passed, rank) = match color {
+                            White => ((self.bb(Black, Pawn) & WHITE_PASSED_PAWN_MASKS[square]).is_empty(), LOOKUP_RANK[square]),
+                            Black => ((self.bb(White, Pawn) & BLACK_PASSED_PAWN_MASKS[square]).is_empty(), 7 - LOOKUP_RANK[square]),
+                        };
+                        if is_passed {
+                            add(color, PASSED_PAWN_OFFSET + rank, 1, 1);
+                        }
+                    },
+                    Knight | Bishop | Rook | Queen => {
+                        let attacks = match piece {
+                            Knight => knight_attacks(square as u8),
+                            Bishop => d12_attacks(square as u8, self.all_occupancies),
+                            Rook => hv_attacks(square as u8, self.all_occupancies),
+                            _ => d12_attacks(square as u8, self.all_occupancies) | hv_attacks(square as u8, self.all_occupancies),
+                        };
+                        let move_cnt = (attacks & !self.color_bb(color)).pop_count() as i16;
+                        add(color, MOBILITY_EARLY_OFFSET + piece as usize - 1, move_cnt, 0);
+                        add(color, MOBILITY_LATE_OFFSET + piece as usize - 1, 0, move_cnt);
+
+                        if piece == Rook {
+                            if (self.bb(color, Pawn) & FILE_MASKS[square]).is_empty() {
+                                add(color, SEMI_OPEN_FILE_OFFSET, 1, 1);
+                            }
+                            if ((self.bb(White, Pawn) | self.bb(Black, Pawn)) & FILE_MASKS[square]).is_empty() {
+                                add(color, OPEN_FILE_OFFSET, 1, 1);
+                            }
+                        }
+                    },
+                    King => {
+                        if (self.bb(color, Pawn) & FILE_MASKS[square]).is_empty() {
+                            add(color, SEMI_OPEN_FILE_OFFSET, -1, -1);
+                        }
+                        if ((self.bb(White, Pawn) | self.bb(Black, Pawn)) & FILE_MASKS[square]).is_empty() {
+                            add(color, OPEN_FILE_OFFSET, -1, -1);
+                        }
+
+                        let protectors = (king_attacks(square as u8) & self.color_bb(color)).pop_count() as i16;
+                        add(color, PROTECTED_KING_EARLY_OFFSET, protectors, 0);
+                        add(color, PROTECTED_KING_LATE_OFFSET, 0, protectors);
+                    },
+                    Empty => unreachable!("Empty piece on board!"),
+                }
+            }
+        }
+
+        let pawn_diff = self.bb(White, Pawn).pop_count() as i16 - self.bb(Black, Pawn).pop_count() as i16;
+
+        EvalFeatures {
+            features,
+            constant: (pawn_diff * MATERIAL_WEIGHTS_EARLY[0], pawn_diff * MATERIAL_WEIGHTS_LATE[0]),
+            phase: self.phase(),
+        }
+    }
+}
+
 pub const EVAL_CONSTS: WeightArray = {
     let mut consts = [0; WEIGHT_COUNT];
     
@@ -0,0 +1,329 @@
+use std::{sync::Arc, fmt::Write};
+
+use super::*;
+
+/// Version of the weights file format. Files with other versions are rejected
+pub const WEIGHTS_FILE_VERSION: u32 = 1;
+const WEIGHTS_FILE_FORMAT: &str = "cadabra-weights";
+
+/// Named sections of a weights file and their sizes, in WeightArray order
+pub const WEIGHT_SECTIONS: [(&str, usize); 23] = [
+    ("material_early", 4),
+    ("material_late", 4),
+    ("pawn_scores_early", 64),
+    ("pawn_scores_late", 64),
+    ("knight_scores_early", 64),
+    ("knight_scores_late", 64),
+    ("bishop_scores_early", 64),
+    ("bishop_scores_late", 64),
+    ("rook_scores_early", 64),
+    ("rook_scores_late", 64),
+    ("queen_scores_early", 64),
+    ("queen_scores_late", 64),
+    ("king_scores_early", 64),
+    ("king_scores_late", 64),
+    ("stacked_pawn_penalty", 1),
+    ("isolated_pawn_penalty", 1),
+    ("passed_pawn_bonus", 8),
+    ("semi_open_file_bonus", 1),
+    ("open_file_bonus", 1),
+    ("protected_king_bonus_early", 1),
+    ("mobility_bonus_early", 4),
+    ("protected_king_bonus_late", 1),
+    ("mobility_bonus_late", 4),
+];
+
+struct EvalTables {
+    material_weights_early: [i16; 12],
+    material_weights_late: [i16; 12],
+    piece_scores_early: [[i16; 64]; 6],
+    piece_scores_late: [[i16; 64]; 6],
+    passed_pawn_bonus: [i16; 8],
+    weights: WeightArray,
+}
+
+/// Evaluator with weights loaded at runtime. Cheap to clone, as the tables are shared
+#[derive(Clone)]
+pub struct HeapEvaluator {
+    tables: Arc<EvalTables>,
+}
+
+impl HeapEvaluator {
+    pub fn from_weights(weights: WeightArray) -> Self {
+        // Pawn material is fixed, and is not part of the weights
+        let material = |offset: usize, pawn: i16| {
+            let mut material = [0; 12];
+            material[0] = pawn;
+            material[1..5].copy_from_slice(&weights[offset..offset + 4]);
+            for i in 0..6 {
+                material[i + 6] = -material[i];
+            }
+            material
+        };
+
+        let pst = |offsets: [usize; 6]| offsets.map(|offset| {
+            let mut table = [0; 64];
+            table.copy_from_slice(&weights[offset..offset + 64]);
+            table
+        });
+
+        let mut passed_pawn_bonus = [0; 8];
+        passed_pawn_bonus.copy_from_slice(&weights[PASSED_PAWN_OFFSET..PASSED_PAWN_OFFSET + 8]);
+
+        Self {
+            tables: Arc::new(EvalTables {
+                material_weights_early: material(MATERIAL_EARLY_OFFSET, MATERIAL_WEIGHTS_EARLY[0]),
+                material_weights_late: material(MATERIAL_LATE_OFFSET, MATERIAL_WEIGHTS_LATE[0]),
+                piece_scores_early: pst(PST_EARLY_OFFSETS),
+                piece_scores_late: pst(PST_LATE_OFFSETS),
+                passed_pawn_bonus,
+                weights,
+            })
+        }
+    }
+
+    pub fn weights(&self) -> WeightArray {
+        self.tables.weights
+    }
+
+    /// Loads a weights file
+    pub fn load(path: &str) -> Result<Self, String> {
+        let content = std::fs::read_to_string(path).map_err(|err| format!("Could not read weights file '{path}': {err}"))?;
+        Self::from_json(&content)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), String> {
+        std::fs::write(path, self.to_json()).map_err(|err| format!("Could not write weights file '{path}': {err}"))
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut json = String::new();
+        writeln!(json, "{{").unwrap();
+        writeln!(json, "  \"format\": \"{WEIGHTS_FILE_FORMAT}\",").unwrap();
+        writeln!(json, "  \"version\": {WEIGHTS_FILE_VERSION},").unwrap();
+        writeln!(json, "  \"sections\": {{").unwrap();
+
+        let mut offset = 0;
+        for (i, (name, size)) in WEIGHT_SECTIONS.iter().enumerate() {
+            let rows = self.tables.weights[offset..offset + size].chunks(8)
+                .map(|row| row.iter().map(|w| w.to_string()).collect::<Vec<_>>().join(", "))
+                .collect::<Vec<_>>();
+
+            let separator = if i + 1 == WEIGHT_SECTIONS.len() { "" } else { "," };
+            if rows.len() == 1 {
+                writeln!(json, "    \"{name}\": [{}]{separator}", rows[0]).unwrap();
+            } else {
+                writeln!(json, "    \"{name}\": [\n      {}\n    ]{separator}", rows.join(",\n      ")).unwrap();
+            }
+
+            offset += size;
+        }
+
+        writeln!(json, "  }}").unwrap();
+        write!(json, "}}").unwrap();
+        json
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, String> {
+        let mut parser = JsonParser { input: json.as_bytes(), pos: 0 };
+        let root = parser.parse()?;
+
+        match root.get("format") {
+            Some(Json::String(format)) if format == WEIGHTS_FILE_FORMAT => (),
+            _ => return Err(format!("Not a weights file. Expected format '{WEIGHTS_FILE_FORMAT}'")),
+        }
+        match root.get("version") {
+            Some(Json::Number(version)) if *version == WEIGHTS_FILE_VERSION as i64 => (),
+            Some(Json::Number(version)) => return Err(format!("Unsupported weights file version {version}. Expected version {WEIGHTS_FILE_VERSION}")),
+            _ => return Err("Weights file has no version".to_string()),
+        }
+        let sections = match root.get("sections") {
+            Some(Json::Object(sections)) => sections,
+            _ => return Err("Weights file has no sections".to_string()),
+        };
+
+        if let Some((name, _)) = sections.iter().find(|(name, _)| !WEIGHT_SECTIONS.iter().any(|(n, _)| n == name)) {
+            return Err(format!("Unknown section '{name}' in weights file"))
+        }
+
+        let mut weights = [0; WEIGHT_COUNT];
+        let mut offset = 0;
+        for (name, size) in WEIGHT_SECTIONS {
+            let values = match sections.iter().find(|(n, _)| n == name) {
+                Some((_, Json::Array(values))) => values,
+                Some(_) => return Err(format!("Section '{name}' must be an array")),
+                None => return Err(format!("Missing section '{name}' in weights file")),
+            };
+            if values.len() != size {
+                return Err(format!("Section '{name}' has {} weights, expected {size}", values.len()))
+            }
+
+            for value in values {
+                weights[offset] = match value {
+                    Json::Number(n) => i16::try_from(*n).map_err(|_| format!("Weight {n} in section '{name}' is out of range"))?,
+                    _ => return Err(format!("Section '{name}' contains a weight that is not an integer")),
+                };
+                offset += 1;
+            }
+        }
+
+        Ok(Self::from_weights(weights))
+    }
+}
+
+impl Default for HeapEvaluator {
+    fn default() -> Self {
+        Self::from_weights(EVAL_CONSTS)
+    }
+}
+
+impl Evaluator for HeapEvaluator {
+    fn material_weights_early(&self) -> &[i16; 12] { &self.tables.material_weights_early }
+    fn material_weights_late(&self) -> &[i16; 12] { &self.tables.material_weights_late }
+    fn pawn_scores_early(&self) -> &[i16; 64] { &self.tables.piece_scores_early[0] }
+    fn pawn_scores_late(&self) -> &[i16; 64] { &self.tables.piece_scores_late[0] }
+    fn knight_scores_early(&self) -> &[i16; 64] { &self.tables.piece_scores_early[1] }
+    fn knight_scores_late(&self) -> &[i16; 64] { &self.tables.piece_scores_late[1] }
+    fn bishop_scores_early(&self) -> &[i16; 64] { &self.tables.piece_scores_early[2] }
+    fn bishop_scores_late(&self) -> &[i16; 64] { &self.tables.piece_scores_late[2] }
+    fn rook_scores_early(&self) -> &[i16; 64] { &self.tables.piece_scores_early[3] }
+    fn rook_scores_late(&self) -> &[i16; 64] { &self.tables.piece_scores_late[3] }
+    fn queen_scores_early(&self) -> &[i16; 64] { &self.tables.piece_scores_early[4] }
+    fn queen_scores_late(&self) -> &[i16; 64] { &self.tables.piece_scores_late[4] }
+    fn king_scores_early(&self) -> &[i16; 64] { &self.tables.piece_scores_early[5] }
+    fn king_scores_late(&self) -> &[i16; 64] { &self.tables.piece_scores_late[5] }
+    fn stacked_pawn_penalty(&self) -> i16 { self.tables.weights[STACKED_PAWN_OFFSET] }
+    fn isolated_pawn_penalty(&self) -> i16 { self.tables.weights[ISOLATED_PAWN_OFFSET] }
+    fn passed_pawn_bonus(&self) -> &[i16; 8] { &self.tables.passed_pawn_bonus }
+    fn semi_open_file_bonus(&self) -> i16 { self.tables.weights[SEMI_OPEN_FILE_OFFSET] }
+    fn open_file_bonus(&self) -> i16 { self.tables.weights[OPEN_FILE_OFFSET] }
+    fn protected_king_bonus_early(&self) -> i16 { self.tables.weights[PROTECTED_KING_EARLY_OFFSET] }
+    fn knight_mobility_bonus_early(&self) -> i16 { self.tables.weights[MOBILITY_EARLY_OFFSET] }
+    fn bishop_mobility_bonus_early(&self) -> i16 { self.tables.weights[MOBILITY_EARLY_OFFSET + 1] }
+    fn rook_mobility_bonus_early(&self) -> i16 { self.tables.weights[MOBILITY_EARLY_OFFSET + 2] }
+    fn queen_mobility_bonus_early(&self) -> i16 { self.tables.weights[MOBILITY_EARLY_OFFSET + 3] }
+    fn protected_king_bonus_late(&self) -> i16 { self.tables.weights[PROTECTED_KING_LATE_OFFSET] }
+    fn knight_mobility_bonus_late(&self) -> i16 { self.tables.weights[MOBILITY_LATE_OFFSET] }
+    fn bishop_mobility_bonus_late(&self) -> i16 { self.tables.weights[MOBILITY_LATE_OFFSET + 1] }
+    fn rook_mobility_bonus_late(&self) -> i16 { self.tables.weights[MOBILITY_LATE_OFFSET + 2] }
+    fn queen_mobility_bonus_late(&self) -> i16 { self.tables.weights[MOBILITY_LATE_OFFSET + 3] }
+}
+
+/// The subset of JSON needed for weights files
+enum Json {
+    Object(Vec<(String, Json)>),
+    Array(Vec<Json>),
+    String(String),
+    Number(i64),
+}
+
+impl Json {
+    fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+}
+
+struct JsonParser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl JsonParser<'_> {
+    fn parse(&mut self) -> Result<Json, String> {
+        let value = self.parse_value()?;
+        self.skip_whitespace();
+        if self.pos != self.input.len() {
+            return Err(self.error("Unexpected trailing characters"))
+        }
+        Ok(value)
+    }
+
+    fn parse_value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => {
+                self.pos += 1;
+                let mut entries = Vec::new();
+                if !self.try_consume(b'}') {
+                    loop {
+                        self.skip_whitespace();
+                        let key = self.parse_string()?;
+                        self.expect(b':')?;
+                        entries.push((key, self.parse_value()?));
+                        if self.try_consume(b'}') { break }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(Json::Object(entries))
+            },
+            Some(b'[') => {
+                self.pos += 1;
+                let mut values = Vec::new();
+                if !self.try_consume(b']') {
+                    loop {
+                        values.push(self.parse_value()?);
+                        if self.try_consume(b']') { break }
+                        self.expect(b',')?;
+                    }
+                }
+                Ok(Json::Array(values))
+            },
+            Some(b'"') => Ok(Json::String(self.parse_string()?)),
+            Some(b'-' | b'0'..=b'9') => {
+                let start = self.pos;
+                self.pos += 1;
+                while let Some(b'0'..=b'9') = self.peek() {
+                    self.pos += 1;
+                }
+                let number = std::str::from_utf8(&self.input[start..self.pos]).unwrap();
+                number.parse().map(Json::Number).map_err(|_| self.error(&format!("Illegal number '{number}'")))
+            },
+            _ => Err(self.error("Expected a value")),
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, String> {
+        self.expect(b'"')?;
+        let start = self.pos;
+        while self.peek().ok_or_else(|| self.error("Unterminated string"))? != b'"' {
+            self.pos += 1;
+        }
+        let string = String::from_utf8_lossy(&self.input[start..self.pos]).to_string();
+        self.pos += 1;
+        Ok(string)
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ' | b'\n' | b'\r' | b'\t') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn try_consume(&mut self, c: u8) -> bool {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            return true
+        }
+        false
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), String> {
+        if self.try_consume(c) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{}'", c as char)))
+        }
+    }
+
+    fn error(&self, msg: &str) -> String {
+        format!("Invalid weights file at byte {}: {msg}", self.pos)
+    }
+}
@@ -11,9 +11,17 @@ mod search_stats;
 mod eval_consts;
 mod evaluator;
 mod quiescence;
+mod search_params;
+mod heap_evaluator;
+mod move_picker;
+mod see;
+mod time_manager;
+mod thread_pool;
+mod skill;
 
 //use transposition_table::*;
 pub use evaluation::*;
+pub use perft::*;
 pub use search::*;
 pub use transposition_table::*;
 pub use search_context::*;
@@ -24,6 +32,13 @@ pub use search_stats::*;
 pub use evaluator::*;
 pub use eval_consts::*;
 pub use quiescence::*;
+pub use search_params::*;
+pub use heap_evaluator::*;
+pub use move_picker::*;
+pub use see::*;
+pub use time_manager::*;
+pub use thread_pool::*;
+pub use skill::*;
 
 use crate::engine::*;
 
@@ -37,18 +52,17 @@ pub const MATE_VALUE: i16 = 29000;
 /// Assume everything between this and MATE_VALUE is mate
 pub const MATE_BOUND: i16 = 28000;
 
-/// Initial aspiration window is +- this value
-pub const ASPIRATION_WINDOW: i32 = 15;
 /// Exponentially increase window by this multiplier on fail
 pub const ASPIRATION_WINDOW_MULT: i32 = 3;
 
-/// Amount of killer moves pr. ply to remember. Must be at least 1
-pub const KILLER_MOVE_COUNT: usize = 3;
+/// LMR is reduced by one for every this much quiet history
+pub const LMR_HISTORY_DIVISOR: i32 = 8192;
 
-/// TT entry age penalty to prioritize newer entries
-pub const AGE_REPLACEMENT_PENALTY: i16 = 8;
+/// The master thread checks the time every this many nodes
+pub const TIME_CHECK_INTERVAL: u128 = 1024;
 
-pub const PRE_FRONTIER_FUTILITY_MARGIN: i16 = 600;
-pub const FRONTIER_FUTILITY_MARGIN: i16 = 150;
+/// Maximum amount of killer moves pr. ply that can be remembered
+pub const MAX_KILLER_MOVES: usize = 8;
 
-pub const NULL_MOVE_R: u8 = 2;
\ No newline at end of file
+/// TT entry age penalty to prioritize newer entries
+pub const AGE_REPLACEMENT_PENALTY: i16 = 8;
\ No newline at end of file
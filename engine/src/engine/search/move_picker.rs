@@ -0,0 +1,157 @@
+use super::*;
+
+/// Piece values used to tell good captures from bad ones. The king can only capture undefended pieces
+const PICKER_PIECE_VALUES: [i16; 6] = [100, 300, 300, 500, 900, 0];
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    TTMove,
+    GenerateCaptures,
+    GoodCaptures,
+    Killers,
+    Countermove,
+    Quiets,
+    BadCaptures,
+    Done,
+}
+
+/// Yields the legal moves of a position in stages, generating them lazily.
+///
+/// The order is: TT move, good captures, killer moves, countermove, quiet moves and bad captures.
+/// This means a cutoff on an early move avoids generating the later ones.
+/// The TT move and killer moves are validated with `is_legal`, so they can be searched before any generation.
+pub struct MovePicker {
+    stage: Stage,
+    tt_move: Move,
+    ply: u8,
+    captures: Option<MoveList>,
+    quiets: Option<MoveList>,
+    bad_captures: Option<MoveList>,
+    killer_index: usize,
+    /// Killer moves and countermove already returned, which are removed from the quiet moves when they are generated
+    used_quiets: heapless::Vec<Move, { MAX_KILLER_MOVES + 1 }>,
+    skip_quiets: bool,
+}
+
+impl MovePicker {
+    pub fn new(tt_move: Move, ply: u8) -> Self {
+        Self {
+            stage: Stage::TTMove,
+            tt_move,
+            ply,
+            captures: None,
+            quiets: None,
+            bad_captures: Some(MoveList::new()),
+            killer_index: 0,
+            used_quiets: heapless::Vec::new(),
+            skip_quiets: false,
+        }
+    }
+
+    pub fn next(&mut self, pos: &Position, context: &mut SearchContext) -> Option<Move> {
+        loop {
+            if self.skip_quiets && matches!(self.stage, Stage::Killers | Stage::Countermove | Stage::Quiets) {
+                self.start_bad_captures(pos, context);
+            }
+
+            match self.stage {
+                Stage::TTMove => {
+                    self.stage = Stage::GenerateCaptures;
+
+                    // Only return the TT move if it is legal here, as it could come from a hash collision
+                    if pos.is_legal(self.tt_move) {
+                        return Some(self.tt_move)
+                    }
+                    self.tt_move = Move::NULL;
+                },
+                Stage::GenerateCaptures => {
+                    let mut captures = pos.generate_captures();
+                    captures.remove(self.tt_move);
+                    self.captures = Some(captures.sort(pos, context, Move::NULL, self.ply));
+                    self.stage = Stage::GoodCaptures;
+                },
+                Stage::GoodCaptures => {
+                    match self.captures.as_mut().unwrap().pop_best() {
+                        Some(capture) if is_bad_capture(pos, capture) => self.bad_captures.as_mut().unwrap().push(capture),
+                        Some(capture) => return Some(capture),
+                        None => self.stage = Stage::Killers,
+                    }
+                },
+                Stage::Killers => {
+                    if self.killer_index >= context.params.killer_move_count {
+                        self.stage = Stage::Countermove;
+                        continue
+                    }
+
+                    let killer = context.killer_moves[self.killer_index][self.ply as usize];
+                    self.killer_index += 1;
+
+                    if let Some(killer) = killer {
+                        if self.is_new_quiet(pos, killer) {
+                            let _ = self.used_quiets.push(killer);
+                            return Some(killer)
+                        }
+                    }
+                },
+                Stage::Countermove => {
+                    let mut quiets = pos.generate_quiets();
+                    quiets.remove(self.tt_move);
+                    for quiet in &self.used_quiets {
+                        quiets.remove(*quiet);
+                    }
+
+                    // As the moves already returned are removed, this also validates the countermove
+                    let countermove = context.countermove(self.ply);
+                    let is_new = quiets.remove(countermove);
+
+                    self.quiets = Some(quiets.sort(pos, context, Move::NULL, self.ply));
+                    self.stage = Stage::Quiets;
+
+                    if is_new {
+                        return Some(countermove)
+                    }
+                },
+                Stage::Quiets => {
+                    match self.quiets.as_mut().unwrap().pop_best() {
+                        Some(quiet) => return Some(quiet),
+                        None => self.start_bad_captures(pos, context),
+                    }
+                },
+                Stage::BadCaptures => {
+                    match self.bad_captures.as_mut().unwrap().pop_best() {
+                        Some(capture) => return Some(capture),
+                        None => self.stage = Stage::Done,
+                    }
+                },
+                Stage::Done => return None,
+            }
+        }
+    }
+
+    /// Stops returning quiet moves. Used when the remaining quiet moves are pruned
+    pub fn skip_quiets(&mut self) {
+        self.skip_quiets = true;
+    }
+
+    fn start_bad_captures(&mut self, pos: &Position, context: &mut SearchContext) {
+        let bad_captures = self.bad_captures.take().unwrap();
+        self.bad_captures = Some(bad_captures.sort(pos, context, Move::NULL, self.ply));
+        self.stage = Stage::BadCaptures;
+    }
+
+    /// Whether the quiet move is legal and has not been returned yet
+    fn is_new_quiet(&self, pos: &Position, moove: Move) -> bool {
+        moove != self.tt_move && !moove.is_capture() && !self.used_quiets.contains(&moove) && pos.is_legal(moove)
+    }
+}
+
+/// A capture is considered bad if the captured piece is worth less than the capturing piece
+fn is_bad_capture(pos: &Position, capture: Move) -> bool {
+    if capture.is_enpassant() {
+        return false
+    }
+
+    let attacker = PICKER_PIECE_VALUES[pos.piece_type_at(capture.src()).index(Color::White)];
+    let victim = PICKER_PIECE_VALUES[pos.piece_type_at(capture.dst()).index(Color::White)];
+    victim < attacker
+}
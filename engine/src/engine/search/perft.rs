@@ -1,5 +1,51 @@
+use std::{sync::atomic::{AtomicU64, AtomicUsize, Ordering::Relaxed}, thread};
+
 use super::*;
 
+/// Mixed into the key of a perft table entry, so the same position at different depths gets different keys
+const PERFT_DEPTH_SALT: u64 = 0x9E37_79B9_7F4A_7C15;
+
+#[derive(Default)]
+struct PerftEntry {
+    /// The key xor the node count, so torn writes from other threads are detected
+    key: AtomicU64,
+    nodes: AtomicU64,
+}
+
+/// Node counts of positions seen in a perft, keyed by zobrist hash and depth. Shared by all perft threads
+pub struct PerftTable {
+    entries: Vec<PerftEntry>,
+}
+
+impl PerftTable {
+    pub fn new(size_mb: usize) -> Self {
+        let len = (size_mb * 1024 * 1024 / std::mem::size_of::<PerftEntry>()).max(1);
+        Self { entries: std::iter::repeat_with(PerftEntry::default).take(len).collect() }
+    }
+
+    fn key(hash: u64, depth: u8) -> u64 {
+        hash ^ PERFT_DEPTH_SALT.wrapping_mul(depth as u64)
+    }
+
+    fn entry(&self, key: u64) -> &PerftEntry {
+        &self.entries[key as usize % self.entries.len()]
+    }
+
+    fn probe(&self, hash: u64, depth: u8) -> Option<u64> {
+        let key = Self::key(hash, depth);
+        let entry = self.entry(key);
+        let nodes = entry.nodes.load(Relaxed);
+        (entry.key.load(Relaxed) ^ nodes == key).then_some(nodes)
+    }
+
+    fn record(&self, hash: u64, depth: u8, nodes: u64) {
+        let key = Self::key(hash, depth);
+        let entry = self.entry(key);
+        entry.key.store(key ^ nodes, Relaxed);
+        entry.nodes.store(nodes, Relaxed);
+    }
+}
+
 impl Position {
     pub fn perft<const ROOT: bool>(&self, depth: u8) -> u64 {
         self.perft_internal::<ROOT>(depth)
@@ -30,4 +76,52 @@ impl Position {
 
         result
     }
-}
\ No newline at end of file
+
+    /// Counts the nodes after each root move. The root moves are split between the threads,
+    /// and the node counts of positions reached in several ways are looked up in the table, if any
+    pub fn perft_divide(&self, depth: u8, threads: usize, table: Option<&PerftTable>) -> Vec<(Move, u64)> {
+        let moves: Vec<Move> = self.generate_moves().collect();
+        let counts: Vec<AtomicU64> = moves.iter().map(|_| AtomicU64::new(0)).collect();
+
+        // Each thread takes the next root move not yet counted
+        let next = AtomicUsize::new(0);
+        thread::scope(|scope| {
+            for _ in 0..threads.clamp(1, moves.len().max(1)) {
+                scope.spawn(|| {
+                    loop {
+                        let i = next.fetch_add(1, Relaxed);
+                        let Some(&moove) = moves.get(i) else { break };
+
+                        let mut copy = *self;
+                        copy.make_move(moove);
+                        counts[i].store(copy.perft_hashed(depth.saturating_sub(1), table), Relaxed);
+                    }
+                });
+            }
+        });
+
+        moves.into_iter().zip(counts).map(|(moove, count)| (moove, count.into_inner())).collect()
+    }
+
+    fn perft_hashed(&self, depth: u8, table: Option<&PerftTable>) -> u64 {
+        match (depth, table) {
+            (0, _) => 1,
+            (1, _) => self.generate_moves().len() as u64,
+            (_, None) => self.perft::<false>(depth),
+            (_, Some(table)) => {
+                if let Some(nodes) = table.probe(self.zobrist_hash, depth) {
+                    return nodes
+                }
+
+                let nodes = self.generate_moves().map(|moove| {
+                    let mut copy = *self;
+                    copy.make_move(moove);
+                    copy.perft_hashed(depth - 1, Some(table))
+                }).sum();
+
+                table.record(self.zobrist_hash, depth, nodes);
+                nodes
+            },
+        }
+    }
+}
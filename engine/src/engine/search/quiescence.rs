@@ -1,46 +1,103 @@
-use crate::{Position, SearchContext, Evaluator, engine::moove::Move};
+use crate::{Position, SearchContext, Evaluator, HashFlag, MATE_VALUE, MAX_DEPTH, SEE_VALUES, piece_index, engine::{moove::Move, PieceType}};
 
+/// Searches captures and queen promotions until the position is quiet. All evasions are searched when in check
 #[inline(always)]
-pub fn quiescence(pos: &Position, mut alpha: i16, beta: i16, ply: u8, context: &mut SearchContext, evaluator: impl Evaluator + Copy) -> i16 {
+pub fn quiescence(pos: &Position, mut alpha: i16, beta: i16, ply: u8, context: &mut SearchContext, evaluator: &impl Evaluator) -> i16 {
     context.nodes += 1;
+    context.counters.qnodes += 1;
 
-    // Evaluate position immediately
-    let eval = pos.evaluate(evaluator);
+    let is_pv = (beta as i32 - alpha as i32) > 1;
 
-    let in_check = pos.is_in_check();
+    // Probe transposition table. Any entry is at least as deep as the quiescence search
+    let mut tt_move = Move::NULL;
+    if let Some(entry) = context.probe_tt(pos.zobrist_hash, ply) {
+        tt_move = entry.best_move;
 
-    if eval > alpha && !in_check {
-        alpha = eval;
+        if !is_pv {
+            match entry.flag {
+                HashFlag::Exact => return entry.score,
+                HashFlag::LowerBound if entry.score >= beta => return entry.score,
+                HashFlag::UpperBound if entry.score <= alpha => return entry.score,
+                _ => (),
+            }
+        }
+    }
+
+    if ply >= MAX_DEPTH {
+        return pos.evaluate(evaluator)
     }
 
+    let in_check = pos.is_in_check();
+
+    // Stand pat. Not allowed in check, as there might not be a legal move keeping the score
+    let eval = if in_check { -MATE_VALUE + ply as i16 } else { pos.evaluate(evaluator) };
+
     if eval >= beta {
         return beta
     }
 
-    // Generate moves
-    let mut move_list = pos.generate_moves().sort(pos, context, Move::NULL, ply);
+    let original_alpha = alpha;
+    if eval > alpha {
+        alpha = eval;
+    }
+
+    // Generate captures and queen promotions, or all evasions if in check
+    let move_list = if in_check {
+        pos.generate_moves()
+    } else {
+        pos.generate_noisy()
+    };
+
+    // Checkmate
+    if in_check && move_list.len() == 0 {
+        return -MATE_VALUE + ply as i16
+    }
+
+    let mut move_list = move_list.sort_noisy(pos, tt_move);
+    let mut best_move = Move::NULL;
 
-    // Loop through all captures
     while let Some(moove) = move_list.pop_best() {
-        if !in_check && (!(moove.is_capture() || moove.is_enpassant())) {
-            continue
+        if !in_check {
+            // Delta pruning. Skip captures that cannot raise alpha, even with a margin for positional gains
+            let captured = if moove.is_enpassant() {
+                SEE_VALUES[PieceType::Pawn as usize]
+            } else if moove.is_capture() {
+                SEE_VALUES[pos.piece_type_at(moove.dst()) as usize]
+            } else {
+                0
+            };
+            if !moove.is_promotion() && (eval as i32 + captured + context.params.delta_margin as i32) < alpha as i32 {
+                continue
+            }
+
+            // Skip moves losing material
+            if !pos.see(moove, 0) {
+                continue
+            }
         }
 
+        context.set_stack_move(ply, Some((piece_index(pos, moove), moove.dst())));
+
         let mut copy = pos.clone();
         copy.make_move(moove);
-        
+
         let score = -quiescence(&copy, -beta, -alpha, ply + 1, context, evaluator);
 
         // Alpha cutoff
         if score > alpha {
             alpha = score;
+            best_move = moove;
 
             // Beta cutoff
             if score >= beta {
+                context.search.tt.record(pos.zobrist_hash, best_move, 0, beta, HashFlag::LowerBound, ply, context.tt_age);
                 return beta;
             }
         }
     }
 
+    let hash_flag = if alpha > original_alpha { HashFlag::Exact } else { HashFlag::UpperBound };
+    context.search.tt.record(pos.zobrist_hash, best_move, 0, alpha, hash_flag, ply, context.tt_age);
+
     alpha
-}
\ No newline at end of file
+}
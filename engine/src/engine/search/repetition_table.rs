@@ -3,15 +3,21 @@ use super::*;
 #[derive(Copy, Clone)]
 pub struct RepetitionTable {
     len: usize,
-    hashes: [u64; 100]
+    /// Room for the 100 reversible half moves allowed by the 50 move rule, the initial position and the search
+    hashes: [u64; 128]
 }
 
 impl RepetitionTable {
     pub fn new() -> Self {
-        Self { len: 0, hashes: [0; 100] }
+        Self { len: 0, hashes: [0; 128] }
     }
 
     pub fn push(&mut self, hash: u64) {
+        // Games continuing past the 50 move rule can fill the table. The oldest positions are then forgotten
+        if self.len == self.hashes.len() {
+            self.hashes.copy_within(1.., 0);
+            self.len -= 1;
+        }
         self.hashes[self.len] = hash;
         self.len += 1;
     }
@@ -26,7 +32,7 @@ impl RepetitionTable {
         if pos.half_moves >= 8 {
             // Count occurences of current position
             let hash = pos.zobrist_hash;
-            let repetitions = self.hashes.iter().filter(|a| **a == hash).count();
+            let repetitions = self.hashes[..self.len].iter().filter(|a| **a == hash).count();
             return repetitions >= 3
         }
         false
@@ -1,11 +1,16 @@
 use super::*;
 
-const PV_MOVE_SCORE: u16 = 30000;
-const BASE_CAPTURE_SCORE: u16 = 29000;
-const BASE_KILLER_SCORE: u16 = 28000;
+const PV_MOVE_SCORE: i32 = 1_000_000;
+const BASE_CAPTURE_SCORE: i32 = 500_000;
+const BASE_KILLER_SCORE: i32 = 400_000;
+const COUNTERMOVE_SCORE: i32 = 300_000;
+
+/// MVV-LVA is scaled by this, so capture history only decides between captures of the same victim
+const MVV_LVA_SCALE: i32 = 32;
+const CAPTURE_HISTORY_DIVISOR: i32 = 8;
 
 ///[attacker][victim]
-pub const MVV_LVA: [[u16; 6]; 6] = [
+pub const MVV_LVA: [[i32; 6]; 6] = [
     [105, 205, 305, 405, 505, 605],
     [104, 204, 304, 404, 504, 604],
     [103, 203, 303, 403, 503, 603],
@@ -16,35 +21,55 @@ pub const MVV_LVA: [[u16; 6]; 6] = [
 
 impl Move {
     #[inline(always)]
-    pub fn score_move(&self, pos: &Position, context: &mut SearchContext, tt_move: Move, ply: u8) -> u16 {
+    pub fn score_move(&self, pos: &Position, context: &mut SearchContext, tt_move: Move, ply: u8) -> i32 {
         if *self == tt_move {
             return PV_MOVE_SCORE
         }
 
-        // Special case for enpassant
-        if self.is_enpassant() {
-            return MVV_LVA[0][0] // Pawn x Pawn
-        }
-    
         let src = self.src();
         let dst = self.dst();
-        
+        let piece = piece_index(pos, *self);
+
         // Captures
         if self.is_capture() {
             let src_piece = pos.piece_type_at(src);
-            let dst_piece = pos.piece_type_at(dst);
-    
-            return MVV_LVA[src_piece.index(Color::White)][dst_piece.index(Color::White)] + BASE_CAPTURE_SCORE
+            // Special case for enpassant
+            let dst_piece = if self.is_enpassant() { PieceType::Pawn } else { pos.piece_type_at(dst) };
+            let history = context.capture_history(piece, dst, dst_piece) / CAPTURE_HISTORY_DIVISOR;
+
+            return MVV_LVA[src_piece.index(Color::White)][dst_piece.index(Color::White)] * MVV_LVA_SCALE + history + BASE_CAPTURE_SCORE
         }
 
         // Killer moves
-        for i in 0..KILLER_MOVE_COUNT {
+        for i in 0..context.params.killer_move_count {
             if Some(*self) == context.killer_moves[i][ply as usize] {
-                return BASE_KILLER_SCORE - i as u16
+                return BASE_KILLER_SCORE - i as i32
             }
         }
 
-        let (color, piece) = pos.piece_at(src);
-        context.history_moves[piece.index(color)][dst as usize].min(1000)
+        if *self == context.countermove(ply) {
+            return COUNTERMOVE_SCORE
+        }
+
+        context.quiet_history(piece, dst, ply)
+    }
+
+    /// Scores captures and promotions by MVV-LVA, with the TT move first. Other moves get 0
+    #[inline(always)]
+    pub fn score_noisy(&self, pos: &Position, tt_move: Move) -> i32 {
+        if *self == tt_move {
+            return PV_MOVE_SCORE
+        }
+
+        let attacker = pos.piece_type_at(self.src()).index(Color::White);
+        if self.is_enpassant() {
+            MVV_LVA[attacker][PieceType::Pawn as usize]
+        } else if self.is_capture() {
+            MVV_LVA[attacker][pos.piece_type_at(self.dst()).index(Color::White)]
+        } else if self.is_promotion() {
+            MVV_LVA[attacker][self.promotion().index(Color::White)]
+        } else {
+            0
+        }
     }
-}
\ No newline at end of file
+}
@@ -1,5 +1,5 @@
 use super::*;
-use std::{sync::{atomic::{Ordering::*, AtomicBool}, Arc, Mutex}, thread::{self, JoinHandle}, time::Instant};
+use std::{sync::{atomic::{Ordering::*, AtomicBool, AtomicU64}, mpsc::channel, Arc, Mutex}, time::Instant};
 
 #[derive(Clone)]
 pub struct Search {
@@ -8,58 +8,94 @@ pub struct Search {
     settings: Arc<Mutex<Settings>>,
     pub tt: Arc<TranspositionTable>,
     pub generation: Arc<Mutex<u8>>,
+    pub pool: Arc<ThreadPool>,
+    /// Nodes searched by all threads in the current search
+    nodes: Arc<AtomicU64>,
+    /// The hash file of the analysis. It is loaded again when the table is rebuilt with its size
+    hash_file: Arc<Mutex<Option<String>>>,
 }
 
 impl Search {
     pub fn new(settings: Settings) -> Self {
-        let tt = TranspositionTable::new(settings.transposition_table_mb);
+        let tt = TranspositionTable::new(settings.transposition_table_mb, settings.threads as usize);
         Self {
             is_running: Arc::new(AtomicBool::new(false)),
             is_stopping: Arc::new(AtomicBool::new(false)),
             settings: Arc::new(Mutex::new(settings)),
             tt: Arc::new(tt),
             generation: Arc::new(Mutex::new(0)),
+            pool: Arc::new(ThreadPool::new(settings.threads)),
+            nodes: Arc::new(AtomicU64::new(0)),
+            hash_file: Arc::new(Mutex::new(None)),
         }
     }
 
-    pub fn update_settings(&mut self, new_settings: Settings) {
-        // New tt if size changed
-        if new_settings.transposition_table_mb != self.settings.lock().unwrap().transposition_table_mb {
-            self.tt = Arc::new(TranspositionTable::new(new_settings.transposition_table_mb));
+    /// Applies the settings. If the table is rebuilt with the size of a loaded hash file, the analysis in it is restored
+    pub fn update_settings(&mut self, new_settings: Settings) -> Result<(), String> {
+        let resized = new_settings.transposition_table_mb != self.settings.lock().unwrap().transposition_table_mb;
+
+        // New workers if thread count changed
+        if new_settings.threads as usize != self.pool.len() {
+            self.pool = Arc::new(ThreadPool::new(new_settings.threads));
         }
 
         *self.settings.lock().unwrap() = new_settings;
+
+        // New tt if size changed
+        if resized {
+            self.tt = Arc::new(TranspositionTable::new(new_settings.transposition_table_mb, new_settings.threads as usize));
+
+            let hash_file = self.hash_file.lock().unwrap().clone();
+            if let Some(path) = hash_file {
+                return self.load_hash(&path)
+            }
+        }
+
+        Ok(())
     }
 
     /// Returns the running time
     pub fn start<E: Evaluator + Send + Clone + 'static>(&self, pos: Position, meta: SearchArgs, print: bool, evaluator: E) -> SearchStats {
         self.is_running.store(true, Relaxed);
+        self.nodes.store(0, Relaxed);
+        let start_time = Instant::now();
+
+        // Limited strength searches only use the master, so the chosen move is not outvoted
+        let threads = if Skill::new(&self.settings()).is_enabled() { 1 } else { self.pool.len() };
 
-        // Spawn worker threads
-        let mut workers = Vec::new();
-        for t in 0..self.settings.lock().unwrap().threads {
+        // Queue the search on the workers
+        let (sender, receiver) = channel();
+        for t in 0..threads {
             let search = self.clone();
-            let meta = meta.clone();
-            let pos = pos.clone();
             let e = evaluator.clone();
+            let sender = sender.clone();
 
-            let h: JoinHandle<SearchStats> = thread::spawn(move || {
-                let mut context = SearchContext::new(search, meta, pos, Instant::now(), print);
-                if t == 0 {
-                    run_search::<true, E>(&mut context, t, e)
-                } else {       
-                    run_search::<false, E>(&mut context, t, e)
+            self.pool.execute(t, Box::new(move |data| {
+                let mut context = SearchContext::new(search, meta, pos, start_time, print);
+                if let Some(data) = data.take() {
+                    context.restore(data);
                 }
-            });
 
-            workers.push(h);
+                let stats = if t == 0 {
+                    run_search::<true, E>(&mut context, t as u8, e)
+                } else {
+                    run_search::<false, E>(&mut context, t as u8, e)
+                };
+
+                // Only keep the move ordering state, as the context holds on to the pool
+                *data = Some(context.into_thread_data());
+                let _ = sender.send(stats);
+            }));
         }
+        drop(sender);
 
         // Wait for all threads to terminate and combine results
-        let result: SearchStats = workers.into_iter().map(|w| match w.join() {
-            Ok(stats) => stats,
-            Err(err) => panic!("Worker thread panicked with error: {:?}", err),
-        }).sum();
+        let results: Vec<SearchStats> = receiver.iter().collect();
+        if results.len() != threads {
+            panic!("Worker thread panicked");
+        }
+        let mut result: SearchStats = results.iter().copied().sum();
+        result.best_move = vote_best_move(&results);
 
         self.is_stopping.store(false, Relaxed);
         self.is_running.store(false, Release);
@@ -78,12 +114,43 @@ impl Search {
         result
     }
 
+    /// Clears the transposition table using all the configured threads, and forgets the loaded hash file
+    pub fn clear_hash(&self) {
+        self.tt.clear(self.settings().threads as usize);
+        *self.hash_file.lock().unwrap() = None;
+    }
+
+    /// Saves the transposition table to a file, so the analysis can be continued later
+    pub fn save_hash(&self, path: &str) -> Result<(), String> {
+        self.tt.save(path, *self.generation.lock().unwrap())
+    }
+
+    /// Restores a transposition table saved by save_hash. The Hash setting must match the size of the saved table.
+    /// Otherwise the file is kept, and loaded when the Hash setting is changed to its size
+    pub fn load_hash(&self, path: &str) -> Result<(), String> {
+        let megabytes = TranspositionTable::file_megabytes(path)?;
+        *self.hash_file.lock().unwrap() = Some(path.to_string());
+
+        let hash_mb = self.settings().transposition_table_mb;
+        if megabytes != hash_mb {
+            return Err(format!("The hash file '{path}' is for a {megabytes} MB table, but the Hash option is {hash_mb} MB. It is loaded when the Hash option is set to {megabytes}"))
+        }
+
+        let generation = self.tt.load(path)?;
+        *self.generation.lock().unwrap() = generation;
+        Ok(())
+    }
+
     pub fn stop(&self) {
         if self.is_running() {
             self.is_stopping.store(true, Relaxed);
         }
     }
 
+    pub fn settings(&self) -> Settings {
+        *self.settings.lock().unwrap()
+    }
+
     pub fn is_running(&self) -> bool {
         self.is_running.load(Acquire)
     }
@@ -91,14 +158,46 @@ impl Search {
     pub fn is_stopping(&self) -> bool {
         self.is_stopping.load(Relaxed)
     }
+
+    /// Total nodes searched by all threads, as far as they have reported them
+    pub fn nodes(&self) -> u128 {
+        self.nodes.load(Relaxed) as u128
+    }
+
+    pub fn add_nodes(&self, nodes: u128) {
+        self.nodes.fetch_add(nodes as u64, Relaxed);
+    }
 }
 
-macro_rules! info {
-    ($context:expr, $($msg: tt)*) => {
-        if IS_MASTER && $context.is_printing {
-            println!($($msg)*)
+/// Each thread votes for its best move, weighted by its depth and how good its score is compared to the other threads
+fn vote_best_move(results: &[SearchStats]) -> Option<Move> {
+    let min_score = results.iter().filter(|r| r.best_move.is_some()).map(|r| r.score as i32).min()?;
+
+    let mut votes: Vec<(Move, i32)> = Vec::new();
+    for result in results {
+        let Some(moove) = result.best_move else { continue };
+        let weight = (result.score as i32 - min_score + 20) * result.depth as i32;
+        match votes.iter_mut().find(|(m, _)| *m == moove) {
+            Some((_, vote)) => *vote += weight,
+            None => votes.push((moove, weight)),
         }
-    };
+    }
+
+    // The first move wins ties
+    votes.into_iter().rev().max_by_key(|(_, vote)| *vote).map(|(moove, _)| moove)
+}
+
+/// Helper threads skip some depths, so they spread out over more depths than the master.
+/// Depth d is skipped if (d + phase) / size is odd
+const SKIP_SIZE: [u8; 20] = [1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 3, 3, 4, 4, 4, 4, 4, 4, 4, 4];
+const SKIP_PHASE: [u8; 20] = [0, 1, 0, 1, 2, 3, 0, 1, 2, 3, 4, 5, 0, 1, 2, 3, 4, 5, 6, 7];
+
+fn skip_depth(thread_id: u8, depth: u8) -> bool {
+    if thread_id == 0 {
+        return false
+    }
+    let i = (thread_id as usize - 1) % SKIP_SIZE.len();
+    (depth + SKIP_PHASE[i]) / SKIP_SIZE[i] % 2 == 1
 }
 
 fn score_str(score: i16) -> String {
@@ -113,12 +212,29 @@ fn score_str(score: i16) -> String {
 
 pub fn run_search<const IS_MASTER: bool, E: Evaluator>(context: &mut SearchContext, thread_id: u8, evaluator: E) -> SearchStats {
     let pos = context.pos;
+    let aspiration_window = context.params.aspiration_window;
 
     let mut best_move = Option::None;
+    let mut completed_depth = 0;
+    let mut best_score = 0;
     let (mut alpha, mut beta) = (-INFINITY, INFINITY);
 
+    // Used by the time manager to stop early when the search is stable
+    let mut best_move_stability = 0;
+    let mut last_score = None;
+    let mut best_move_time = 0;
+
+    // The best root moves and their scores from the last iteration, when the strength is limited
+    let mut skill_lines: Vec<(Move, i16)> = Vec::new();
+
     // Iterative deepening loop
-    for depth in (thread_id % 4 + 1)..=(context.search_meta.max_depth) {
+    for depth in 1..=(context.search_meta.max_depth) {
+        if skip_depth(thread_id, depth) {
+            continue;
+        }
+
+        let iteration_start = context.nodes;
+
         // Run initial search with narrow search (Except first time)
         let mut score = negamax::<IS_MASTER, E>(&pos, alpha, beta, depth, 0, context, &evaluator);
 
@@ -133,18 +249,18 @@ pub fn run_search<const IS_MASTER: bool, E: Evaluator>(context: &mut SearchConte
             if score <= alpha {
                 // Widen window alpha side
                 alpha_mult *= ASPIRATION_WINDOW_MULT;
-                alpha = (score as i32 - alpha_mult * ASPIRATION_WINDOW).max(-INFINITY as i32) as i16;
+                alpha = (score as i32 - alpha_mult * aspiration_window).max(-INFINITY as i32) as i16;
                 score = negamax::<IS_MASTER, E>(&pos, alpha, beta, depth, 0, context, &evaluator);
             } else if score >= beta {
                 // Widen window beta side
                 beta_mult *= ASPIRATION_WINDOW_MULT;
-                beta = (score as i32 + beta_mult * ASPIRATION_WINDOW).min(INFINITY as i32) as i16;
+                beta = (score as i32 + beta_mult * aspiration_window).min(INFINITY as i32) as i16;
                 score = negamax::<IS_MASTER, E>(&pos, alpha, beta, depth, 0, context, &evaluator);
 
             } else {
                 // Succcessful search
                 // Reset window for next iteration
-                (alpha, beta) = (score - ASPIRATION_WINDOW as i16, score + ASPIRATION_WINDOW as i16);
+                (alpha, beta) = (score - aspiration_window as i16, score + aspiration_window as i16);
                 break;
             }
         }
@@ -153,10 +269,42 @@ pub fn run_search<const IS_MASTER: bool, E: Evaluator>(context: &mut SearchConte
             break;
         }
         
-        best_move = context.pv_table.best_move();
-
+        let new_best_move = context.pv_table.best_move();
         let time = context.start_time.elapsed().as_millis();
-        info!(context, "info score {} depth {depth} nodes {} time {} pv {}", score_str(score), context.nodes, time, context.pv_table);
+        if new_best_move == best_move {
+            best_move_stability += 1;
+        } else {
+            best_move_stability = 0;
+            best_move_time = time;
+        }
+        best_move = new_best_move;
+
+        context.counters.previous_iteration_nodes = context.counters.iteration_nodes;
+        context.counters.iteration_nodes = context.nodes - iteration_start;
+
+        completed_depth = depth;
+        best_score = score;
+
+        if IS_MASTER && context.is_printing {
+            context.report_nodes();
+            let nodes = context.search.nodes();
+            println!("info score {} depth {depth} nodes {nodes} nps {} time {time} pv {}", score_str(score), nodes * 1000 / time.max(1), context.pv_table);
+        }
+
+        if context.skill.is_enabled() {
+            let lines = search_next_root_moves::<IS_MASTER, E>(context, best_move, score, depth, &evaluator);
+            // Only use a partial iteration if nothing else is available
+            if !context.search.is_stopping() || skill_lines.is_empty() {
+                skill_lines = lines;
+            }
+        }
+
+        // Don't start a new iteration if it is unlikely to finish or change the best move
+        let score_drop = last_score.map_or(0, |last: i16| last as i32 - score as i32);
+        if IS_MASTER && context.time.should_stop_iterating(time, best_move_stability, score_drop) {
+            break;
+        }
+        last_score = Some(score);
     }
 
     // Stop helper threads
@@ -164,17 +312,61 @@ pub fn run_search<const IS_MASTER: bool, E: Evaluator>(context: &mut SearchConte
         context.search.stop();
     };
 
+    if !skill_lines.is_empty() {
+        best_move = Some(context.skill.pick_move(&skill_lines));
+    }
+
     SearchStats {
         nodes: context.nodes,
         tt_hits: context.tt_hits,
         time: context.start_time.elapsed().as_millis(),
         best_move,
+        best_move_time,
+        depth: completed_depth,
+        score: best_score,
+        counters: context.counters,
     }
 }
 
+/// Searches the root moves after the best one, excluding the moves already found, like a MultiPV search.
+/// Returns the best root moves sorted by score
+fn search_next_root_moves<const IS_MASTER: bool, E: Evaluator>(context: &mut SearchContext, best_move: Option<Move>, score: i16, depth: u8, evaluator: &E) -> Vec<(Move, i16)> {
+    let Some(best_move) = best_move else {
+        return Vec::new()
+    };
+
+    let pos = context.pos;
+    let lines_wanted = context.skill.multi_pv().min(pos.generate_moves().len());
+    let mut lines = vec![(best_move, score)];
+
+    context.root_excluded.clear();
+    let _ = context.root_excluded.push(best_move);
+
+    while lines.len() < lines_wanted {
+        let score = negamax::<IS_MASTER, E>(&pos, -INFINITY, INFINITY, depth, 0, context, evaluator);
+        if context.search.is_stopping() {
+            break;
+        }
+
+        // The PV table is not updated if no move was found
+        let Some(moove) = context.pv_table.best_move().filter(|m| !context.root_excluded.contains(m)) else {
+            break;
+        };
+
+        lines.push((moove, score));
+        let _ = context.root_excluded.push(moove);
+    }
+
+    context.root_excluded.clear();
+
+    // The later searches can find better moves than the earlier ones, as the TT fills up
+    lines.sort_by_key(|(_, score)| -score);
+    lines
+}
+
 pub fn negamax<const IS_MASTER: bool, E: Evaluator>(pos: &Position, mut alpha: i16, mut beta: i16, mut depth: u8, ply: u8, context: &mut SearchContext, evaluator: &E) -> i16 {
     // Stop search if signalled or time ran out
-    if IS_MASTER && context.exceeded_time_target() {
+    if context.exceeded_time(IS_MASTER) {
         context.search.stop();
         return 0
     } else if context.search.is_stopping() { // Cancel search
@@ -194,8 +386,8 @@ pub fn negamax<const IS_MASTER: bool, E: Evaluator>(pos: &Position, mut alpha: i
     }
 
     // Detect 50 move rule, 3 fold repetition and insufficient material stalemates
-    if ply > 0 && (pos.half_moves == 100 || pos.rep_table.is_in_3_fold_rep(pos) || pos.is_insufficient_material()) {
-        return 0
+    if ply > 0 && (pos.half_moves >= 100 || pos.rep_table.is_in_3_fold_rep(pos) || pos.is_insufficient_material()) {
+        return context.draw_score(pos)
     }
 
     // Check extension
@@ -205,17 +397,23 @@ pub fn negamax<const IS_MASTER: bool, E: Evaluator>(pos: &Position, mut alpha: i
     // Run quiescence search if the desired depth is reached
     if depth == 0 || ply >= MAX_DEPTH as u8 {
         context.nodes -= 1; // Adjust node count to avoid double counting
-        return quiescence(pos, alpha, beta, ply, context, CONST_EVALUATOR);
+        return quiescence(pos, alpha, beta, ply, context, evaluator);
     };
     
     // Check if we are in a PV node
     let is_pv = (beta as i32 - alpha as i32) > 1;
 
+    // A move is excluded when searching for singular extensions. The TT entry belongs to the full search, so it is not used
+    let excluded_move = context.excluded_moves[ply as usize];
+    let is_excluding = !excluded_move.is_null() || (ply == 0 && !context.root_excluded.is_empty());
+
     // Probe transposition table
     let mut tt_move = Move::NULL;
-    if let Some(entry) = context.search.tt.probe(pos.zobrist_hash, ply) {
-        context.tt_hits += 1;
+    let mut tt_entry = None;
+    let tt_probe = if is_excluding { None } else { context.probe_tt(pos.zobrist_hash, ply) };
+    if let Some(entry) = tt_probe {
         tt_move = entry.best_move;
+        tt_entry = Some((entry.score, entry.depth, entry.flag));
         
         // !is_pv, or we get weird stuff happening
         if !is_pv {
@@ -234,52 +432,154 @@ pub fn negamax<const IS_MASTER: bool, E: Evaluator>(pos: &Position, mut alpha: i
         }
     }
 
+    // Internal iterative reductions. Without a TT move the ordering is worse, so spend less time on the node
+    if context.params.internal_iterative_reductions && tt_move.is_null() && depth >= context.params.iir_min_depth {
+        depth -= 1;
+    }
+
     // Initialize TT entry hashflag
     let mut hash_flag = HashFlag::UpperBound;
 
     // Do a static evaluation for later use
-    let static_eval = pos.evaluate(CONST_EVALUATOR);
+    let static_eval = pos.evaluate(evaluator);
 
     // Reverse futility pruning
-    let can_futility_prune = !in_check && !is_pv;
+    let can_futility_prune = !in_check && !is_pv && !is_excluding;
     if can_futility_prune {
         if depth == 1 {
-            if static_eval - FRONTIER_FUTILITY_MARGIN >= beta {
-                return static_eval - FRONTIER_FUTILITY_MARGIN
+            if static_eval - context.params.frontier_futility_margin >= beta {
+                return static_eval - context.params.frontier_futility_margin
             }
         } else if depth == 2 {
-            if static_eval - PRE_FRONTIER_FUTILITY_MARGIN >= beta {
-                return static_eval - PRE_FRONTIER_FUTILITY_MARGIN
+            if static_eval - context.params.pre_frontier_futility_margin >= beta {
+                return static_eval - context.params.pre_frontier_futility_margin
             }
         }
     }
 
+    // Razoring. Drop into quiescence search if the static eval is far below alpha
+    let can_razor = context.params.razoring
+        && !is_pv
+        && !is_excluding
+        && !in_check
+        && depth <= context.params.razoring_max_depth
+        && (static_eval as i32 + context.params.razoring_margin as i32 * depth as i32) < alpha as i32;
+
+    if can_razor {
+        let score = quiescence(pos, alpha - 1, alpha, ply, context, evaluator);
+
+        if score < alpha {
+            return score
+        }
+    }
+
     // Null move pruning
+    let null_move_r = context.params.null_move_r;
     let only_pawns_left = pos.bb(pos.active_color, PieceType::Pawn).pop_count() + 1 == pos.color_bb(pos.active_color).pop_count();
     let can_nmp = !is_pv
+        && !is_excluding
         && !in_check 
-        && depth >= NULL_MOVE_R + 1
+        && depth >= null_move_r + 1
         && !only_pawns_left 
         && static_eval >= beta;
 
     if can_nmp {
+        context.counters.null_move_tries += 1;
         let mut new_pos = *pos;
         new_pos.make_null_move();
+        context.set_stack_move(ply, None);
 
-        let score = -negamax::<IS_MASTER, E>(&new_pos, -beta, -beta + 1, depth - 1 - NULL_MOVE_R, ply + 1, context, evaluator);
+        let score = -negamax::<IS_MASTER, E>(&new_pos, -beta, -beta + 1, depth - 1 - null_move_r, ply + 1, context, evaluator);
 
         if score >= beta {
+            context.counters.null_move_cutoffs += 1;
             return beta
         }
     }
 
-    // Generate moves
-    let mut move_list = pos.generate_moves().sort(pos, context, tt_move, ply);
+    // Moves are generated lazily in stages
+    let mut move_picker = MovePicker::new(tt_move, ply);
+
+    // Moves searched so far, which get a history malus if another move causes a cutoff
+    let mut quiets_searched: heapless::Vec<Move, MAX_MOVES> = heapless::Vec::new();
+    let mut captures_searched: heapless::Vec<Move, MAX_MOVES> = heapless::Vec::new();
 
     // Main move loop
     let mut moves_searched = 0;
-    while let Some(moove) = move_list.pop_best() {
+    while let Some(moove) = move_picker.next(pos, context) {
+        if moove == excluded_move || (ply == 0 && context.root_excluded.contains(&moove)) {
+            continue
+        }
+
         moves_searched += 1;
+        if moves_searched == 1 {
+            context.counters.expanded_nodes += 1;
+        }
+
+        // Prune late moves, once a move has been searched and we are not getting mated
+        let is_quiet = !moove.is_capture() && !moove.is_promotion();
+        if !is_pv && !in_check && moves_searched > 1 && alpha > -MATE_BOUND {
+            let params = &context.params;
+
+            if is_quiet {
+                // Late move pruning
+                let lmp_moves = params.lmp_base as u32 + depth as u32 * depth as u32;
+                if params.late_move_pruning && depth <= params.lmp_max_depth && moves_searched as u32 > lmp_moves {
+                    move_picker.skip_quiets();
+                    continue
+                }
+
+                // Futility pruning
+                let futility_margin = params.futility_base as i32 + params.futility_margin as i32 * depth as i32;
+                if params.futility_pruning && depth <= params.futility_max_depth && static_eval as i32 + futility_margin <= alpha as i32 {
+                    move_picker.skip_quiets();
+                    continue
+                }
+            }
+
+            // SEE pruning of moves losing too much material
+            if params.see_pruning && depth <= params.see_max_depth {
+                let margin = if is_quiet { params.see_quiet_margin } else { params.see_capture_margin };
+                if !pos.see(moove, -margin * depth as i32) {
+                    continue
+                }
+            }
+        }
+
+        // Singular extensions. If the TT move is much better than all other moves in a reduced search, it is extended
+        let mut extension: i32 = 0;
+        if let Some((tt_score, tt_depth, tt_flag)) = tt_entry.filter(|_| moove == tt_move) {
+            let params = &context.params;
+            let can_singular_extend = params.singular_extensions
+                && ply > 0
+                && depth >= params.singular_min_depth
+                && tt_flag != HashFlag::UpperBound
+                && tt_depth + params.singular_tt_depth_margin >= depth
+                && tt_score.abs() < MATE_BOUND;
+
+            if can_singular_extend {
+                let singular_beta = tt_score - (params.singular_margin as i32 * depth as i32) as i16;
+                let singular_depth = (depth - 1) / 2;
+
+                context.excluded_moves[ply as usize] = moove;
+                let score = negamax::<IS_MASTER, E>(pos, singular_beta - 1, singular_beta, singular_depth, ply, context, evaluator);
+                context.excluded_moves[ply as usize] = Move::NULL;
+
+                if score < singular_beta {
+                    extension = 1;
+                } else if singular_beta >= beta {
+                    // Multi-cut. Several moves beat beta, so this node will most likely fail high
+                    return singular_beta
+                } else if tt_score >= beta {
+                    // Negative extension. The TT move is not singular, and another move is likely to cause the cutoff
+                    extension = -1;
+                }
+            }
+        }
+        let new_depth = (depth as i32 - 1 + extension) as u8;
+
+        let piece = piece_index(pos, moove);
+        context.set_stack_move(ply, Some((piece, moove.dst())));
 
         let mut new_pos = *pos;
         new_pos.make_move(moove);
@@ -288,44 +588,62 @@ pub fn negamax<const IS_MASTER: bool, E: Evaluator>(pos: &Position, mut alpha: i
         let mut score;
         if moves_searched == 0 {
             // Full search in left-most node
-            score = -negamax::<IS_MASTER, E>(&new_pos, -beta, -alpha, depth - 1, ply + 1, context, evaluator);
+            score = -negamax::<IS_MASTER, E>(&new_pos, -beta, -alpha, new_depth, ply + 1, context, evaluator);
         } else {
             // Late move reductions
             // Determine if LMR should be used
             let can_lmr = !is_pv 
-                && depth >= 3
+                && depth >= context.params.lmr_min_depth
                 && !moove.is_capture() 
                 && !moove.is_promotion() 
                 && !in_check 
                 && !caused_check 
-                && moves_searched >= 4;
+                && moves_searched >= context.params.lmr_min_moves;
 
             if can_lmr {
-                // Reduced null window search
-                let reduction = if moves_searched >= 6 { 2 } else { 1 };
-
-                score = -negamax::<IS_MASTER, E>(&new_pos, -alpha - 1, -alpha, depth - 1 - reduction, ply + 1, context, evaluator);
+                // Reduced null window search. Moves with a good history are reduced less
+                let reduction = if context.params.log_lmr {
+                    context.lmr_table[depth.min(63) as usize][moves_searched.min(63) as usize] as i32
+                } else if moves_searched >= context.params.lmr_deep_moves {
+                    2
+                } else {
+                    1
+                };
+                let history = context.quiet_history(piece, moove.dst(), ply);
+                let reduction = (reduction - history / LMR_HISTORY_DIVISOR).clamp(0, depth as i32 - 1) as u8;
+
+                context.counters.lmr_searches += 1;
+                score = -negamax::<IS_MASTER, E>(&new_pos, -alpha - 1, -alpha, new_depth.saturating_sub(reduction), ply + 1, context, evaluator);
 
                 if score > alpha {
+                    context.counters.lmr_researches += 1;
+
                     // Full null window search on failure
-                    score = -negamax::<IS_MASTER, E>(&new_pos, -alpha - 1, -alpha, depth - 1, ply + 1, context, evaluator);
+                    score = -negamax::<IS_MASTER, E>(&new_pos, -alpha - 1, -alpha, new_depth, ply + 1, context, evaluator);
 
                     if score > alpha {
                         // Full search on failure
-                        score = -negamax::<IS_MASTER, E>(&new_pos, -beta, -alpha, depth - 1, ply + 1, context, evaluator);
+                        score = -negamax::<IS_MASTER, E>(&new_pos, -beta, -alpha, new_depth, ply + 1, context, evaluator);
                     }
                 }
             } else {
                 // Full null window search
-                score = -negamax::<IS_MASTER, E>(&new_pos, -alpha - 1, -alpha, depth - 1, ply + 1, context, evaluator);
+                score = -negamax::<IS_MASTER, E>(&new_pos, -alpha - 1, -alpha, new_depth, ply + 1, context, evaluator);
 
                 if score > alpha {
                     // Full search on failure
-                    score = -negamax::<IS_MASTER, E>(&new_pos, -beta, -alpha, depth - 1, ply + 1, context, evaluator);
+                    score = -negamax::<IS_MASTER, E>(&new_pos, -beta, -alpha, new_depth, ply + 1, context, evaluator);
                 }
             }
         }
 
+        // The lists have room for every move in the move list
+        if moove.is_capture() {
+            let _ = captures_searched.push(moove);
+        } else {
+            let _ = quiets_searched.push(moove);
+        }
+
         // Alpha cutoff
         if score > alpha {
             tt_move = moove;
@@ -335,15 +653,22 @@ pub fn negamax<const IS_MASTER: bool, E: Evaluator>(pos: &Position, mut alpha: i
 
             // Beta cutoff
             if score >= beta {
-                // Record killer move
+                context.counters.beta_cutoffs += 1;
+                if moves_searched == 1 {
+                    context.counters.first_move_cutoffs += 1;
+                }
+
+                // Record killer move and update histories
                 if !moove.is_capture() {
                     context.insert_killer_move(moove, ply);
-                    let (color, piece) = pos.piece_at(moove.src());
-                    context.insert_history_move(moove, (color, piece), depth)
+                    context.update_quiet_histories(pos, moove, &quiets_searched, depth, ply);
                 }
+                context.update_capture_histories(pos, moove, &captures_searched, depth);
 
                 // Record lower bound score in TT
-                context.search.tt.record(pos.zobrist_hash, tt_move, depth, beta, HashFlag::LowerBound, ply, context.tt_age);
+                if !is_excluding {
+                    context.search.tt.record(pos.zobrist_hash, tt_move, depth, beta, HashFlag::LowerBound, ply, context.tt_age);
+                }
                 
                 // Return early
                 return beta;
@@ -359,7 +684,10 @@ pub fn negamax<const IS_MASTER: bool, E: Evaluator>(pos: &Position, mut alpha: i
 
     // Detect mate or stalemate if there are no legal moves
     if moves_searched == 0 {
-        if in_check {
+        if is_excluding {
+            // The excluded move is the only legal move
+            return alpha
+        } else if in_check {
             alpha = -MATE_VALUE + ply as i16;
         }
         else {
@@ -368,7 +696,9 @@ pub fn negamax<const IS_MASTER: bool, E: Evaluator>(pos: &Position, mut alpha: i
     }
     
     // Record upper bound/exact score in TT depending on if we have a PV node
-    context.search.tt.record(pos.zobrist_hash, tt_move, depth, alpha, hash_flag, ply, context.tt_age);
+    if !is_excluding {
+        context.search.tt.record(pos.zobrist_hash, tt_move, depth, alpha, hash_flag, ply, context.tt_age);
+    }
     
     alpha
 }
\ No newline at end of file
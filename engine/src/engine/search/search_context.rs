@@ -2,6 +2,27 @@ use std::time::Instant;
 
 use super::*;
 
+/// History scores are kept between -HISTORY_MAX and HISTORY_MAX by the gravity formula
+pub const HISTORY_MAX: i32 = 16384;
+/// Largest bonus or malus given to a history entry at once
+const HISTORY_BONUS_MAX: i32 = 1536;
+
+/// Indexed by [piece][dst]
+pub type HistoryTable = [[i16; 64]; 12];
+/// Indexed by [previous piece][previous dst][piece][dst]
+pub type ContinuationHistory = [[HistoryTable; 64]; 12];
+/// Indexed by [piece][dst][captured piece type]
+pub type CaptureHistory = [[[i16; 6]; 64]; 12];
+
+/// Move ordering state kept by a search thread between searches
+pub struct ThreadData {
+    pub killer_moves: [[Option<Move>; MAX_DEPTH as usize]; MAX_KILLER_MOVES],
+    pub history_moves: HistoryTable,
+    pub countermoves: [[Move; 64]; 12],
+    pub continuation_history: Box<ContinuationHistory>,
+    pub capture_history: Box<CaptureHistory>,
+}
+
 #[derive(Clone)]
 pub struct SearchContext {
     pub search: Search,
@@ -9,47 +30,225 @@ pub struct SearchContext {
     pub pos: Position,
     pub tt_age: u8,
     pub pv_table: PVTable,
-    pub killer_moves: [[Option<Move>; MAX_DEPTH as usize]; KILLER_MOVE_COUNT],
-    pub history_moves: [[u16; 64]; 12],
+    pub params: SearchParams,
+    pub contempt: i16,
+    pub skill: Skill,
+    /// Root moves skipped while searching for the next best root moves
+    pub root_excluded: heapless::Vec<Move, SKILL_MULTI_PV>,
+    /// Log formula late move reductions, indexed by [depth][moves searched]
+    pub lmr_table: [[u8; 64]; 64],
+    pub killer_moves: [[Option<Move>; MAX_DEPTH as usize]; MAX_KILLER_MOVES],
+    /// The move skipped at each ply while searching for singular extensions, or the null move
+    pub excluded_moves: [Move; MAX_DEPTH as usize],
+    pub history_moves: HistoryTable,
+    /// The quiet move that refuted the previous move, indexed by [previous piece][previous dst]
+    pub countermoves: [[Move; 64]; 12],
+    pub continuation_history: Box<ContinuationHistory>,
+    pub capture_history: Box<CaptureHistory>,
+    /// The piece index and destination of the move made at each ply, or None for null moves
+    pub move_stack: [Option<(usize, u8)>; MAX_DEPTH as usize],
     pub start_time: Instant,
+    pub time: TimeManager,
+    /// Node count at which the time is checked and the nodes are added to the shared count next
+    next_time_check: u128,
+    /// Nodes already added to the shared node count
+    reported_nodes: u128,
     pub is_printing: bool,
 
     pub nodes: u128,
     pub tt_hits : u128,
+    pub counters: SearchCounters,
 }
 
 impl SearchContext {
     pub fn new(search: Search, search_meta: SearchArgs, pos: Position, start_time: Instant, is_printing: bool) -> Self {
         let tt_generation = *search.generation.lock().unwrap();
+        let settings = search.settings();
+        let params = settings.search_params;
+        let time = TimeManager::new(search_meta.time_control, settings.move_overhead);
+        let skill = Skill::new(&settings);
+        let search_meta = skill.limit(search_meta);
         Self {
             search,
             search_meta,
             pos,
             tt_age: tt_generation,
             pv_table: PVTable::new(),
-            killer_moves: [[None; MAX_DEPTH as usize]; KILLER_MOVE_COUNT],
+            lmr_table: params.lmr_table(),
+            params,
+            contempt: settings.contempt,
+            skill,
+            root_excluded: heapless::Vec::new(),
+            killer_moves: [[None; MAX_DEPTH as usize]; MAX_KILLER_MOVES],
+            excluded_moves: [Move::NULL; MAX_DEPTH as usize],
             history_moves: [[0; 64]; 12],
+            countermoves: [[Move::NULL; 64]; 12],
+            continuation_history: vec![[[[0; 64]; 12]; 64]; 12].into_boxed_slice().try_into().unwrap(),
+            capture_history: Box::new([[[0; 6]; 64]; 12]),
+            move_stack: [None; MAX_DEPTH as usize],
             start_time,
+            time,
+            next_time_check: TIME_CHECK_INTERVAL,
+            reported_nodes: 0,
             is_printing,
             nodes: 0,
             tt_hits: 0,
+            counters: SearchCounters::default(),
+        }
+    }
+
+    /// Returns true if the master has used its maximum time or node limit. Every TIME_CHECK_INTERVAL nodes the time is checked,
+    /// and the nodes of this thread are added to the count shared by all threads
+    pub fn exceeded_time(&mut self, is_master: bool) -> bool {
+        if self.nodes < self.next_time_check {
+            return false
         }
+
+        self.next_time_check = self.nodes + TIME_CHECK_INTERVAL;
+        self.report_nodes();
+        is_master && (self.time.exceeded_maximum(self.start_time.elapsed().as_millis()) || self.search.nodes() >= self.search_meta.max_nodes)
+    }
+
+    /// Probes the transposition table, and counts the probe, hit or collision
+    pub fn probe_tt(&mut self, hash: u64, ply: u8) -> Option<EntryData> {
+        self.counters.tt_probes += 1;
+        let entry = self.search.tt.probe(hash, ply);
+        if entry.is_some() {
+            self.tt_hits += 1;
+        } else if self.search.tt.is_occupied(hash) {
+            self.counters.tt_collisions += 1;
+        }
+        entry
+    }
+
+    /// Adds the nodes searched since the last report to the shared node count
+    pub fn report_nodes(&mut self) {
+        self.search.add_nodes(self.nodes - self.reported_nodes);
+        self.reported_nodes = self.nodes;
     }
 
-    /// Returns true if the search should continue, as well as mark as stopping if the time is up
-    pub fn exceeded_time_target(&self) -> bool {
-        self.start_time.elapsed().as_millis() > self.search_meta.time_target
+    /// Takes over the history and killers of a previous search on this thread
+    pub fn restore(&mut self, data: ThreadData) {
+        self.killer_moves = data.killer_moves;
+        self.history_moves = data.history_moves;
+        self.countermoves = data.countermoves;
+        self.continuation_history = data.continuation_history;
+        self.capture_history = data.capture_history;
+    }
+
+    /// The state worth keeping for the next search on this thread
+    pub fn into_thread_data(self) -> ThreadData {
+        ThreadData {
+            killer_moves: self.killer_moves,
+            history_moves: self.history_moves,
+            countermoves: self.countermoves,
+            continuation_history: self.continuation_history,
+            capture_history: self.capture_history,
+        }
+    }
+
+    /// Score of a draw for the side to move. Contempt is applied against the side to move at the root.
+    /// The node count gives a jitter of one centipawn either way, so that not all repetitions look the same
+    pub fn draw_score(&self, pos: &Position) -> i16 {
+        let contempt = if pos.active_color == self.pos.active_color { -self.contempt } else { self.contempt };
+        contempt + (self.nodes & 2) as i16 - 1
     }
 
     pub fn insert_killer_move(&mut self, moove: Move, ply: u8) {
-        for i in (1..KILLER_MOVE_COUNT).rev() {
+        for i in (1..self.params.killer_move_count).rev() {
             self.killer_moves[i][ply as usize] = self.killer_moves[i - 1][ply as usize];
         }
 
         self.killer_moves[0][ply as usize] = Some(moove);
     }
 
-    pub fn insert_history_move(&mut self, moove: Move, (color, piece): (Color, PieceType), depth: u8) {
-        self.history_moves[piece.index(color)][moove.dst() as usize] += (depth * depth) as u16;
+    /// Remembers the move made at this ply, so the children can look up their continuation histories
+    pub fn set_stack_move(&mut self, ply: u8, entry: Option<(usize, u8)>) {
+        if let Some(slot) = self.move_stack.get_mut(ply as usize) {
+            *slot = entry;
+        }
+    }
+
+    /// The move made `back` plies before the node at this ply
+    fn stack_move(&self, ply: u8, back: u8) -> Option<(usize, u8)> {
+        if ply < back {
+            return None
+        }
+        self.move_stack.get((ply - back) as usize).copied().flatten()
+    }
+
+    /// Gets the refutation of the previous move, or the null move if there is none
+    pub fn countermove(&self, ply: u8) -> Move {
+        match self.stack_move(ply, 1) {
+            Some((piece, dst)) => self.countermoves[piece][dst as usize],
+            None => Move::NULL,
+        }
     }
-}
\ No newline at end of file
+
+    /// Combined history score of a quiet move, from the main history and the continuation histories of the last 2 plies
+    pub fn quiet_history(&self, piece: usize, dst: u8, ply: u8) -> i32 {
+        let mut score = self.history_moves[piece][dst as usize] as i32;
+        for back in 1..=2 {
+            if let Some((prev_piece, prev_dst)) = self.stack_move(ply, back) {
+                score += self.continuation_history[prev_piece][prev_dst as usize][piece][dst as usize] as i32;
+            }
+        }
+        score
+    }
+
+    pub fn capture_history(&self, piece: usize, dst: u8, victim: PieceType) -> i32 {
+        self.capture_history[piece][dst as usize][victim.index(Color::White)] as i32
+    }
+
+    /// Rewards the quiet move that caused a beta cutoff, and punishes the quiet moves searched before it
+    pub fn update_quiet_histories(&mut self, pos: &Position, best: Move, searched: &[Move], depth: u8, ply: u8) {
+        let bonus = history_bonus(depth);
+
+        for &moove in searched {
+            let piece = piece_index(pos, moove);
+            let dst = moove.dst();
+            let delta = if moove == best { bonus } else { -bonus };
+
+            apply_gravity(&mut self.history_moves[piece][dst as usize], delta);
+            for back in 1..=2 {
+                if let Some((prev_piece, prev_dst)) = self.stack_move(ply, back) {
+                    apply_gravity(&mut self.continuation_history[prev_piece][prev_dst as usize][piece][dst as usize], delta);
+                }
+            }
+        }
+
+        if let Some((prev_piece, prev_dst)) = self.stack_move(ply, 1) {
+            self.countermoves[prev_piece][prev_dst as usize] = best;
+        }
+    }
+
+    /// Rewards the capture that caused a beta cutoff if any, and punishes the other captures searched
+    pub fn update_capture_histories(&mut self, pos: &Position, best: Move, searched: &[Move], depth: u8) {
+        let bonus = history_bonus(depth);
+
+        for &moove in searched {
+            let piece = piece_index(pos, moove);
+            let victim = if moove.is_enpassant() { PieceType::Pawn } else { pos.piece_type_at(moove.dst()) };
+            let delta = if moove == best { bonus } else { -bonus };
+
+            apply_gravity(&mut self.capture_history[piece][moove.dst() as usize][victim.index(Color::White)], delta);
+        }
+    }
+}
+
+/// The index of the piece making the move
+#[inline(always)]
+pub fn piece_index(pos: &Position, moove: Move) -> usize {
+    let (color, piece) = pos.piece_at(moove.src());
+    piece.index(color)
+}
+
+fn history_bonus(depth: u8) -> i32 {
+    (depth as i32 * depth as i32 * 16).min(HISTORY_BONUS_MAX)
+}
+
+/// Moves the entry towards the bonus, shrinking it the closer the entry is to HISTORY_MAX
+fn apply_gravity(entry: &mut i16, bonus: i32) {
+    let value = *entry as i32;
+    *entry = (value + bonus - value * bonus.abs() / HISTORY_MAX) as i16;
+}
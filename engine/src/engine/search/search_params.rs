@@ -0,0 +1,250 @@
+use super::*;
+
+/// Search parameters that can be changed at runtime.
+///
+/// Exposed as UCI options when built with the 'tune' feature, so they can be tuned by SPSA.
+/// The pruning techniques can also be toggled, so their strength can be measured.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct SearchParams {
+    /// Initial aspiration window is +- this value
+    pub aspiration_window: i32,
+    pub null_move_r: u8,
+    pub frontier_futility_margin: i16,
+    pub pre_frontier_futility_margin: i16,
+    /// Minimum depth for late move reductions
+    pub lmr_min_depth: u8,
+    /// Moves searched before late move reductions are applied
+    pub lmr_min_moves: u8,
+    /// Moves searched before the reduction is increased to 2 plies, when the log formula is not used
+    pub lmr_deep_moves: u8,
+    /// Constant part of the log formula reductions, in hundredths of a ply
+    pub lmr_base: i32,
+    /// The log formula reductions are ln(depth) * ln(moves) divided by this, in hundredths
+    pub lmr_divisor: i32,
+    /// Amount of killer moves pr. ply to remember. Must be between 1 and MAX_KILLER_MOVES
+    pub killer_move_count: usize,
+    /// Quiet moves are pruned after lmp_base + depth^2 moves
+    pub lmp_base: u8,
+    pub lmp_max_depth: u8,
+    /// Quiet moves are pruned if the static eval is this plus futility_margin pr. depth below alpha
+    pub futility_base: i16,
+    pub futility_margin: i16,
+    pub futility_max_depth: u8,
+    /// Drop into quiescence search if the static eval is this pr. depth below alpha
+    pub razoring_margin: i16,
+    pub razoring_max_depth: u8,
+    /// Captures losing more than this pr. depth in the static exchange evaluation are pruned
+    pub see_capture_margin: i32,
+    /// Quiet moves losing more than this pr. depth in the static exchange evaluation are pruned
+    pub see_quiet_margin: i32,
+    pub see_max_depth: u8,
+    /// Captures in quiescence search are skipped if the eval plus the captured piece plus this is below alpha
+    pub delta_margin: i16,
+    /// Minimum depth for internal iterative reductions
+    pub iir_min_depth: u8,
+    /// Minimum depth for singular extensions
+    pub singular_min_depth: u8,
+    /// The TT entry must be at most this many plies shallower than the node to test for singularity
+    pub singular_tt_depth_margin: u8,
+    /// Other moves must score this much pr. depth below the TT score for the TT move to be singular
+    pub singular_margin: i16,
+
+    pub late_move_pruning: bool,
+    pub futility_pruning: bool,
+    pub razoring: bool,
+    pub see_pruning: bool,
+    pub internal_iterative_reductions: bool,
+    pub singular_extensions: bool,
+    /// Use the log formula for late move reductions instead of reducing by 1 or 2 plies
+    pub log_lmr: bool,
+}
+
+impl Default for SearchParams {
+    fn default() -> Self {
+        Self {
+            aspiration_window: 15,
+            null_move_r: 2,
+            frontier_futility_margin: 150,
+            pre_frontier_futility_margin: 600,
+            lmr_min_depth: 3,
+            lmr_min_moves: 4,
+            lmr_deep_moves: 6,
+            lmr_base: 75,
+            lmr_divisor: 225,
+            killer_move_count: 3,
+            lmp_base: 3,
+            lmp_max_depth: 8,
+            futility_base: 100,
+            futility_margin: 100,
+            futility_max_depth: 6,
+            razoring_margin: 250,
+            razoring_max_depth: 3,
+            see_capture_margin: 100,
+            see_quiet_margin: 60,
+            see_max_depth: 8,
+            delta_margin: 200,
+            iir_min_depth: 4,
+            singular_min_depth: 8,
+            singular_tt_depth_margin: 3,
+            singular_margin: 2,
+            late_move_pruning: true,
+            futility_pruning: true,
+            razoring: true,
+            see_pruning: true,
+            internal_iterative_reductions: true,
+            singular_extensions: true,
+            log_lmr: true,
+        }
+    }
+}
+
+impl SearchParams {
+    /// Name, minimum and maximum of every parameter
+    pub const SPECS: [(&'static str, i32, i32); 25] = [
+        ("AspirationWindow", 1, 200),
+        ("NullMoveR", 1, 5),
+        ("FrontierFutilityMargin", 0, 1000),
+        ("PreFrontierFutilityMargin", 0, 2000),
+        ("LmrMinDepth", 1, 10),
+        ("LmrMinMoves", 1, 20),
+        ("LmrDeepMoves", 1, 40),
+        ("LmrBase", 0, 300),
+        ("LmrDivisor", 100, 600),
+        ("KillerMoveCount", 1, MAX_KILLER_MOVES as i32),
+        ("LmpBase", 0, 20),
+        ("LmpMaxDepth", 0, 16),
+        ("FutilityBase", 0, 500),
+        ("FutilityMargin", 0, 500),
+        ("FutilityMaxDepth", 0, 16),
+        ("RazoringMargin", 0, 1000),
+        ("RazoringMaxDepth", 0, 8),
+        ("SeeCaptureMargin", 0, 500),
+        ("SeeQuietMargin", 0, 500),
+        ("SeeMaxDepth", 0, 16),
+        ("DeltaMargin", 0, 1000),
+        ("IirMinDepth", 1, 16),
+        ("SingularMinDepth", 1, 20),
+        ("SingularTtDepthMargin", 0, 10),
+        ("SingularMargin", 0, 20),
+    ];
+
+    /// Names of the pruning techniques that can be turned on and off
+    pub const TOGGLES: [&'static str; 7] = [
+        "LateMovePruning",
+        "FutilityPruning",
+        "Razoring",
+        "SeePruning",
+        "InternalIterativeReductions",
+        "SingularExtensions",
+        "LogLmr",
+    ];
+
+    pub fn get(&self, name: &str) -> Option<i32> {
+        Some(match name {
+            "AspirationWindow" => self.aspiration_window,
+            "NullMoveR" => self.null_move_r as i32,
+            "FrontierFutilityMargin" => self.frontier_futility_margin as i32,
+            "PreFrontierFutilityMargin" => self.pre_frontier_futility_margin as i32,
+            "LmrMinDepth" => self.lmr_min_depth as i32,
+            "LmrMinMoves" => self.lmr_min_moves as i32,
+            "LmrDeepMoves" => self.lmr_deep_moves as i32,
+            "LmrBase" => self.lmr_base,
+            "LmrDivisor" => self.lmr_divisor,
+            "KillerMoveCount" => self.killer_move_count as i32,
+            "LmpBase" => self.lmp_base as i32,
+            "LmpMaxDepth" => self.lmp_max_depth as i32,
+            "FutilityBase" => self.futility_base as i32,
+            "FutilityMargin" => self.futility_margin as i32,
+            "FutilityMaxDepth" => self.futility_max_depth as i32,
+            "RazoringMargin" => self.razoring_margin as i32,
+            "RazoringMaxDepth" => self.razoring_max_depth as i32,
+            "SeeCaptureMargin" => self.see_capture_margin,
+            "SeeQuietMargin" => self.see_quiet_margin,
+            "SeeMaxDepth" => self.see_max_depth as i32,
+            "DeltaMargin" => self.delta_margin as i32,
+            "IirMinDepth" => self.iir_min_depth as i32,
+            "SingularMinDepth" => self.singular_min_depth as i32,
+            "SingularTtDepthMargin" => self.singular_tt_depth_margin as i32,
+            "SingularMargin" => self.singular_margin as i32,
+            _ => return None,
+        })
+    }
+
+    pub fn set(&mut self, name: &str, value: i32) -> Result<(), String> {
+        let (_, min, max) = Self::SPECS.iter().find(|(n, _, _)| *n == name).ok_or(format!("Unknown option name '{name}'"))?;
+        if value < *min || value > *max {
+            return Err(format!("{name} must be between {min} and {max}"))
+        }
+
+        match name {
+            "AspirationWindow" => self.aspiration_window = value,
+            "NullMoveR" => self.null_move_r = value as u8,
+            "FrontierFutilityMargin" => self.frontier_futility_margin = value as i16,
+            "PreFrontierFutilityMargin" => self.pre_frontier_futility_margin = value as i16,
+            "LmrMinDepth" => self.lmr_min_depth = value as u8,
+            "LmrMinMoves" => self.lmr_min_moves = value as u8,
+            "LmrDeepMoves" => self.lmr_deep_moves = value as u8,
+            "LmrBase" => self.lmr_base = value,
+            "LmrDivisor" => self.lmr_divisor = value,
+            "KillerMoveCount" => self.killer_move_count = value as usize,
+            "LmpBase" => self.lmp_base = value as u8,
+            "LmpMaxDepth" => self.lmp_max_depth = value as u8,
+            "FutilityBase" => self.futility_base = value as i16,
+            "FutilityMargin" => self.futility_margin = value as i16,
+            "FutilityMaxDepth" => self.futility_max_depth = value as u8,
+            "RazoringMargin" => self.razoring_margin = value as i16,
+            "RazoringMaxDepth" => self.razoring_max_depth = value as u8,
+            "SeeCaptureMargin" => self.see_capture_margin = value,
+            "SeeQuietMargin" => self.see_quiet_margin = value,
+            "SeeMaxDepth" => self.see_max_depth = value as u8,
+            "DeltaMargin" => self.delta_margin = value as i16,
+            "IirMinDepth" => self.iir_min_depth = value as u8,
+            "SingularMinDepth" => self.singular_min_depth = value as u8,
+            "SingularTtDepthMargin" => self.singular_tt_depth_margin = value as u8,
+            "SingularMargin" => self.singular_margin = value as i16,
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    pub fn get_toggle(&self, name: &str) -> Option<bool> {
+        Some(match name {
+            "LateMovePruning" => self.late_move_pruning,
+            "FutilityPruning" => self.futility_pruning,
+            "Razoring" => self.razoring,
+            "SeePruning" => self.see_pruning,
+            "InternalIterativeReductions" => self.internal_iterative_reductions,
+            "SingularExtensions" => self.singular_extensions,
+            "LogLmr" => self.log_lmr,
+            _ => return None,
+        })
+    }
+
+    pub fn set_toggle(&mut self, name: &str, value: bool) -> Result<(), String> {
+        match name {
+            "LateMovePruning" => self.late_move_pruning = value,
+            "FutilityPruning" => self.futility_pruning = value,
+            "Razoring" => self.razoring = value,
+            "SeePruning" => self.see_pruning = value,
+            "InternalIterativeReductions" => self.internal_iterative_reductions = value,
+            "SingularExtensions" => self.singular_extensions = value,
+            "LogLmr" => self.log_lmr = value,
+            _ => return Err(format!("Unknown option name '{name}'")),
+        }
+
+        Ok(())
+    }
+
+    /// Builds the log formula reduction table, indexed by [depth][moves searched]
+    pub fn lmr_table(&self) -> [[u8; 64]; 64] {
+        let mut table = [[0; 64]; 64];
+        for (depth, row) in table.iter_mut().enumerate().skip(1) {
+            for (moves, reduction) in row.iter_mut().enumerate().skip(1) {
+                let r = self.lmr_base as f64 / 100. + (depth as f64).ln() * (moves as f64).ln() / (self.lmr_divisor as f64 / 100.);
+                *reduction = r.max(0.) as u8;
+            }
+        }
+        table
+    }
+}
@@ -3,22 +3,123 @@ use std::{ops::Add, iter::Sum};
 use crate::engine::moove::Move;
 
 // Result statistics of a search
+#[derive(Clone, Copy)]
 pub struct SearchStats {
     pub nodes: u128,
     pub tt_hits: u128,
     pub time: u128, // millis
     pub best_move: Option<Move>,
+    /// Millis until the iteration that found the final best move completed
+    pub best_move_time: u128,
+    /// Last completed depth
+    pub depth: u8,
+    pub score: i16,
+    pub counters: SearchCounters,
+}
+
+/// Counts of search events, used to measure move ordering and pruning quality
+#[derive(Clone, Copy, Default)]
+pub struct SearchCounters {
+    /// Nodes searched by quiescence search. These are included in the total node count
+    pub qnodes: u128,
+    pub tt_probes: u128,
+    /// Probes missing because the slot holds another position
+    pub tt_collisions: u128,
+    /// Main search nodes where at least one move was searched
+    pub expanded_nodes: u128,
+    pub beta_cutoffs: u128,
+    /// Beta cutoffs caused by the first move searched
+    pub first_move_cutoffs: u128,
+    pub null_move_tries: u128,
+    pub null_move_cutoffs: u128,
+    pub lmr_searches: u128,
+    /// Reduced searches that beat alpha, and had to be searched again
+    pub lmr_researches: u128,
+    /// Nodes of the last completed iteration, and of the one before it
+    pub iteration_nodes: u128,
+    pub previous_iteration_nodes: u128,
+}
+
+impl Add<Self> for SearchCounters {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self {
+            qnodes: self.qnodes + rhs.qnodes,
+            tt_probes: self.tt_probes + rhs.tt_probes,
+            tt_collisions: self.tt_collisions + rhs.tt_collisions,
+            expanded_nodes: self.expanded_nodes + rhs.expanded_nodes,
+            beta_cutoffs: self.beta_cutoffs + rhs.beta_cutoffs,
+            first_move_cutoffs: self.first_move_cutoffs + rhs.first_move_cutoffs,
+            null_move_tries: self.null_move_tries + rhs.null_move_tries,
+            null_move_cutoffs: self.null_move_cutoffs + rhs.null_move_cutoffs,
+            lmr_searches: self.lmr_searches + rhs.lmr_searches,
+            lmr_researches: self.lmr_researches + rhs.lmr_researches,
+            iteration_nodes: self.iteration_nodes + rhs.iteration_nodes,
+            previous_iteration_nodes: self.previous_iteration_nodes + rhs.previous_iteration_nodes,
+        }
+    }
+}
+
+impl SearchStats {
+    /// Share of the expanded nodes that failed high
+    pub fn beta_cutoff_rate(&self) -> f64 {
+        ratio(self.counters.beta_cutoffs, self.counters.expanded_nodes)
+    }
+
+    /// Share of the beta cutoffs caused by the first move. The higher, the better the move ordering
+    pub fn first_move_cutoff_rate(&self) -> f64 {
+        ratio(self.counters.first_move_cutoffs, self.counters.beta_cutoffs)
+    }
+
+    pub fn tt_hit_rate(&self) -> f64 {
+        ratio(self.tt_hits, self.counters.tt_probes)
+    }
+
+    pub fn tt_collision_rate(&self) -> f64 {
+        ratio(self.counters.tt_collisions, self.counters.tt_probes)
+    }
+
+    pub fn qnode_share(&self) -> f64 {
+        ratio(self.counters.qnodes, self.nodes)
+    }
+
+    /// Share of the null move searches that caused a cutoff
+    pub fn null_move_success_rate(&self) -> f64 {
+        ratio(self.counters.null_move_cutoffs, self.counters.null_move_tries)
+    }
+
+    /// Share of the reduced searches that did not need a re-search
+    pub fn lmr_success_rate(&self) -> f64 {
+        ratio(self.counters.lmr_searches - self.counters.lmr_researches, self.counters.lmr_searches)
+    }
+
+    /// Effective branching factor. How many times more nodes the last iteration took than the one before it
+    pub fn branching_factor(&self) -> f64 {
+        ratio(self.counters.iteration_nodes, self.counters.previous_iteration_nodes)
+    }
+}
+
+/// 0 if nothing was counted
+fn ratio(count: u128, total: u128) -> f64 {
+    if total == 0 { 0. } else { count as f64 / total as f64 }
 }
 
 impl Add<Self> for SearchStats {
     type Output = Self;
 
+    /// Sums the counts, and keeps the result of the deepest search
     fn add(self, rhs: Self) -> Self::Output {
+        let deepest = if rhs.depth > self.depth { rhs } else { self };
         Self {
             nodes: self.nodes + rhs.nodes,
             tt_hits: self.tt_hits + rhs.tt_hits,
             time: self.time.max(rhs.time),
-            best_move: None
+            best_move: deepest.best_move,
+            best_move_time: deepest.best_move_time,
+            depth: deepest.depth,
+            score: deepest.score,
+            counters: self.counters + rhs.counters,
         }
     }
 }
@@ -27,4 +128,4 @@ impl Sum for SearchStats {
     fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
         iter.into_iter().reduce(|acc, res| acc + res).unwrap()
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,102 @@
+use super::*;
+
+use PieceType::*;
+
+/// Piece values used by the static exchange evaluation. The king can never be captured
+pub const SEE_VALUES: [i32; 6] = [100, 300, 300, 500, 900, 20000];
+
+impl Position {
+    /// Static exchange evaluation. Determines whether the exchange sequence on the destination square
+    /// wins at least `threshold`, when both sides always recapture with their least valuable piece.
+    ///
+    /// Promotions and castling are assumed to be even exchanges.
+    pub fn see(&self, moove: Move, threshold: i32) -> bool {
+        if moove.is_promotion() || moove.is_castle_ks() || moove.is_castle_qs() {
+            return threshold <= 0
+        }
+
+        let src = moove.src();
+        let dst = moove.dst();
+
+        let victim = if moove.is_enpassant() { Pawn } else { self.piece_type_at(dst) };
+        let mut swap = if victim == Empty { 0 } else { SEE_VALUES[victim as usize] } - threshold;
+        if swap < 0 {
+            return false
+        }
+
+        swap = SEE_VALUES[self.piece_type_at(src) as usize] - swap;
+        if swap <= 0 {
+            return true
+        }
+
+        let mut occ = self.all_occupancies.as_u64() ^ (1 << src) ^ (1 << dst);
+        if moove.is_enpassant() {
+            occ ^= 1 << if self.active_color.is_white() { dst + 8 } else { dst - 8 };
+        }
+
+        let bishops = self.bb(Color::White, Bishop).as_u64() | self.bb(Color::Black, Bishop).as_u64()
+            | self.bb(Color::White, Queen).as_u64() | self.bb(Color::Black, Queen).as_u64();
+        let rooks = self.bb(Color::White, Rook).as_u64() | self.bb(Color::Black, Rook).as_u64()
+            | self.bb(Color::White, Queen).as_u64() | self.bb(Color::Black, Queen).as_u64();
+
+        let mut attackers = self.attackers_to(dst, occ);
+        let mut color = self.active_color;
+        let mut result = true;
+
+        loop {
+            color = color.opposite();
+            attackers &= occ;
+
+            let color_attackers = attackers & self.color_bb(color).as_u64();
+            if color_attackers == 0 {
+                break
+            }
+
+            // Recapture with the least valuable attacker
+            let Some(piece) = [Pawn, Knight, Bishop, Rook, Queen, King].into_iter()
+                .find(|piece| color_attackers & self.bb(color, *piece).as_u64() != 0) else { break };
+
+            result = !result;
+
+            if piece == King {
+                // The king can only recapture if the square is no longer defended
+                if attackers & self.color_bb(color.opposite()).as_u64() != 0 {
+                    result = !result;
+                }
+                break
+            }
+
+            swap = SEE_VALUES[piece as usize] - swap;
+            if swap < result as i32 {
+                break
+            }
+
+            let bb = color_attackers & self.bb(color, piece).as_u64();
+            occ ^= bb & bb.wrapping_neg();
+
+            // Add the sliders behind the removed piece
+            if matches!(piece, Pawn | Bishop | Queen) {
+                attackers |= d12_attacks(dst, Bitboard(occ)) & bishops;
+            }
+            if matches!(piece, Rook | Queen) {
+                attackers |= hv_attacks(dst, Bitboard(occ)) & rooks;
+            }
+        }
+
+        result
+    }
+
+    /// All pieces of both colors attacking the square with the given occupancies
+    pub fn attackers_to(&self, square: u8, occ: u64) -> u64 {
+        let bb = |color: Color, piece: PieceType| self.bb(color, piece).as_u64();
+        let bishops = bb(Color::White, Bishop) | bb(Color::Black, Bishop) | bb(Color::White, Queen) | bb(Color::Black, Queen);
+        let rooks = bb(Color::White, Rook) | bb(Color::Black, Rook) | bb(Color::White, Queen) | bb(Color::Black, Queen);
+
+        pawn_attacks(square, Color::Black) & bb(Color::White, Pawn)
+            | pawn_attacks(square, Color::White) & bb(Color::Black, Pawn)
+            | knight_attacks(square) & (bb(Color::White, Knight) | bb(Color::Black, Knight))
+            | king_attacks(square) & (bb(Color::White, King) | bb(Color::Black, King))
+            | d12_attacks(square, Bitboard(occ)) & bishops
+            | hv_attacks(square, Bitboard(occ)) & rooks
+    }
+}
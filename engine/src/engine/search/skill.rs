@@ -0,0 +1,122 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use super::*;
+
+/// Full strength. Lower levels are limited
+pub const MAX_SKILL_LEVEL: u8 = 20;
+
+/// Estimated Elo of each skill level below MAX_SKILL_LEVEL. UCI_Elo picks the level closest to it.
+///
+/// Estimated from a few short self-play matches between the levels, from random 8 ply openings, with 4 moves below -1000
+/// adjudicated as lost:
+///
+///   Levels   Games   Score   Elo difference
+///   0 - 2      80    0.169       277
+///   2 - 4      80    0.281       163
+///   4 - 6      80    0.231       209
+///   6 - 8      80    0.188       255
+///   8 - 10     80    0.237       203
+///  10 - 12     60    0.267       176
+///  12 - 15     32    0.125       338
+///  15 - 18     16    0.375        89
+///
+/// Levels in between are interpolated, and level 19 is extrapolated. The standard error of a difference is about 50 Elo
+/// for the 80 game matches, and about 90 Elo for the 12 - 15 and 15 - 18 matches, so the errors add up to a couple of
+/// hundred Elo over the table. The scale is anchored by setting level 19, which searches about 2M nodes pr. move like
+/// a blitz game, to 1970, the CCRL Blitz rating of version 2.0
+const SKILL_ELOS: [u16; MAX_SKILL_LEVEL as usize] = [
+    230, 370, 510, 590, 670, 780, 880, 1010, 1130, 1240, 1340, 1430, 1510, 1630, 1740, 1850, 1880, 1910, 1940, 1970
+];
+
+/// UCI_Elo range, from the weakest to the strongest limited level
+pub const MIN_ELO: u16 = SKILL_ELOS[0];
+pub const MAX_ELO: u16 = SKILL_ELOS[MAX_SKILL_LEVEL as usize - 1];
+pub const DEFAULT_ELO: u16 = 1500;
+
+/// Root moves searched at limited strength, that the played move is chosen among
+pub const SKILL_MULTI_PV: usize = 4;
+
+/// Depth cap for each skill level below MAX_SKILL_LEVEL. It is one more than the depth `bench skill` reaches with the node cap,
+/// so the node cap binds in most positions and every level searches more than the one below it. The depth cap limits simple positions
+const SKILL_DEPTHS: [u8; MAX_SKILL_LEVEL as usize] = [2, 2, 3, 3, 4, 4, 5, 5, 6, 7, 7, 8, 8, 9, 10, 10, 11, 11, 12, 13];
+/// Node cap at skill level 0. It grows by SKILL_NODE_GROWTH pr. level
+const SKILL_BASE_NODES: f64 = 1000.;
+const SKILL_NODE_GROWTH: f64 = 1.5;
+
+/// Score differences larger than this do not make the random selection more random
+const MAX_SKILL_DELTA: i32 = 100;
+
+/// Strength limiting from the Skill Level, or from UCI_Elo when UCI_LimitStrength is set.
+///
+/// A limited search gets a depth and node cap, and searches the best few root moves.
+/// The move played is chosen randomly among them, favoring moves close to the best score more the higher the level.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Skill {
+    pub level: u8,
+}
+
+impl Skill {
+    pub fn new(settings: &Settings) -> Self {
+        let level = if settings.limit_strength {
+            (0..MAX_SKILL_LEVEL).min_by_key(|level| SKILL_ELOS[*level as usize].abs_diff(settings.elo)).unwrap()
+        } else {
+            settings.skill_level.min(MAX_SKILL_LEVEL)
+        };
+
+        Self { level }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.level < MAX_SKILL_LEVEL
+    }
+
+    /// Root moves to search each iteration
+    pub fn multi_pv(&self) -> usize {
+        if self.is_enabled() { SKILL_MULTI_PV } else { 1 }
+    }
+
+    /// Applies the depth and node caps of the level to the search arguments
+    pub fn limit(&self, mut args: SearchArgs) -> SearchArgs {
+        if !self.is_enabled() {
+            return args
+        }
+
+        let nodes = (SKILL_BASE_NODES * SKILL_NODE_GROWTH.powi(self.level as i32)) as u128;
+        args.max_depth = args.max_depth.min(SKILL_DEPTHS[self.level as usize]);
+        args.max_nodes = args.max_nodes.min(nodes);
+        args
+    }
+
+    /// Chooses among the root moves, sorted by score with the best first.
+    /// Every move gets a random bonus, which is larger for lower levels and when the scores are far apart
+    pub fn pick_move(&self, lines: &[(Move, i16)]) -> Move {
+        let top = lines[0].1 as i32;
+        let delta = (top - lines[lines.len() - 1].1 as i32).min(MAX_SKILL_DELTA);
+        let weakness = 120 - 2 * self.level as i32;
+
+        let mut rng = random_seed();
+        let mut best = (lines[0].0, i32::MIN);
+        for &(moove, score) in lines {
+            let random = (xorshift(&mut rng) % weakness as u64) as i32;
+            let push = (weakness * (top - score as i32) + delta * random) / 128;
+
+            if score as i32 + push > best.1 {
+                best = (moove, score as i32 + push);
+            }
+        }
+
+        best.0
+    }
+}
+
+fn random_seed() -> u64 {
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64);
+    nanos | 1 // Xorshift must not be seeded with 0
+}
+
+fn xorshift(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
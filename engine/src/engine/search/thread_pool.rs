@@ -0,0 +1,45 @@
+use std::{sync::mpsc::{channel, Sender}, thread};
+
+use super::*;
+
+/// Work sent to a worker. It gets the state the worker kept from its previous search, if any
+pub type Job = Box<dyn FnOnce(&mut Option<ThreadData>) + Send>;
+
+/// Long lived search threads. Each worker keeps its own ThreadData between searches.
+/// The workers exit when the pool is dropped
+pub struct ThreadPool {
+    workers: Vec<Sender<Job>>,
+}
+
+impl ThreadPool {
+    pub fn new(threads: u8) -> Self {
+        let workers = (0..threads.max(1)).map(|_| {
+            let (sender, receiver) = channel::<Job>();
+            thread::spawn(move || {
+                let mut data = None;
+                for job in receiver {
+                    job(&mut data);
+                }
+            });
+            sender
+        }).collect();
+
+        Self { workers }
+    }
+
+    pub fn len(&self) -> usize {
+        self.workers.len()
+    }
+
+    /// Queues the job on the given worker. Jobs on the same worker run in order
+    pub fn execute(&self, worker: usize, job: Job) {
+        self.workers[worker].send(job).expect("Search worker has exited");
+    }
+
+    /// Forgets the history and killers of all workers
+    pub fn clear(&self) {
+        for worker in 0..self.len() {
+            self.execute(worker, Box::new(|data| *data = None));
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use super::*;
+
+/// Moves assumed to be left in the game when the time control does not say
+const DEFAULT_MOVES_TO_GO: u128 = 30;
+/// The maximum time is at most this many times the base time pr. move
+const MAXIMUM_TIME_SCALE: u128 = 4;
+
+/// How much of the optimum time to use after this many iterations with the same best move
+const STABILITY_SCALES: [f64; 5] = [2.0, 1.3, 1.0, 0.85, 0.75];
+/// A score drop of this many centipawns since the last iteration doubles the optimum time
+const SCORE_DROP_SCALE: i32 = 100;
+
+/// Decides how long a search may take.
+///
+/// The search is stopped once the maximum time is exceeded.
+/// No new iterations are started after the optimum time, which is scaled down when the best move is stable
+/// and up when the score drops.
+#[derive(Copy, Clone, Debug)]
+pub struct TimeManager {
+    /// Milliseconds, or None for infinite searches
+    pub optimum: Option<u128>,
+    pub maximum: Option<u128>,
+}
+
+impl TimeManager {
+    pub fn new(time_control: TimeControl, move_overhead: u128) -> Self {
+        match time_control {
+            TimeControl::Infinite => Self { optimum: None, maximum: None },
+            TimeControl::MoveTime(movetime) => {
+                // Iterations are started until the time is used
+                let time = movetime.saturating_sub(move_overhead).max(1);
+                Self { optimum: None, maximum: Some(time) }
+            },
+            TimeControl::Clock { time_left, inc, movestogo } => {
+                let moves_to_go = movestogo.map_or(DEFAULT_MOVES_TO_GO, |m| m as u128);
+                let available = time_left.saturating_sub(move_overhead).max(1);
+
+                let base = available / moves_to_go + inc * 3 / 4;
+                let maximum = (base * MAXIMUM_TIME_SCALE).min(available * 3 / 4).max(1);
+                let optimum = base.min(maximum);
+
+                Self { optimum: Some(optimum), maximum: Some(maximum) }
+            },
+        }
+    }
+
+    /// Whether the search must stop now
+    pub fn exceeded_maximum(&self, elapsed: u128) -> bool {
+        self.maximum.is_some_and(|maximum| elapsed >= maximum)
+    }
+
+    /// Whether a new iteration should be started, given the iterations the best move has been the same and the score drop since the last iteration
+    pub fn should_stop_iterating(&self, elapsed: u128, best_move_stability: usize, score_drop: i32) -> bool {
+        let Some(optimum) = self.optimum else {
+            return false
+        };
+
+        let stability_scale = STABILITY_SCALES[best_move_stability.min(STABILITY_SCALES.len() - 1)];
+        let score_scale = 1. + score_drop.clamp(0, SCORE_DROP_SCALE) as f64 / SCORE_DROP_SCALE as f64;
+
+        elapsed as f64 >= optimum as f64 * stability_scale * score_scale
+    }
+}
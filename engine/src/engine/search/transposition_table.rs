@@ -1,8 +1,16 @@
 use std::{mem::{size_of, self}, sync::atomic::AtomicU64};
 use std::sync::atomic::Ordering::*;
+use std::{fs::File, io::{BufReader, BufWriter, Read, Write}};
+use std::{alloc::{self, Layout}, ptr::NonNull, slice, thread};
 
 use super::*;
 
+/// Version of the hash file format. Files with other versions are rejected
+pub const HASH_FILE_VERSION: u32 = 1;
+const HASH_FILE_MAGIC: &[u8; 8] = b"CDBRHASH";
+/// Magic, version, hash fingerprint, entry count and generation
+const HASH_FILE_HEADER_SIZE: usize = 8 + 4 + 8 + 8 + 1;
+
 #[derive(PartialEq, Debug, Copy, Clone)]
 pub enum HashFlag {
     Exact,
@@ -53,6 +61,16 @@ impl AtomicEntry {
         EntryData::new_from_data(hash, data)
     }
 
+    /// The stored words as they are, for saving the table to a file
+    fn raw(&self) -> (u64, u64) {
+        (self.hash.load(Relaxed), self.data.load(Relaxed))
+    }
+
+    fn store_raw(&self, hash: u64, data: u64) {
+        self.hash.store(hash, Relaxed);
+        self.data.store(data, Relaxed);
+    }
+
     fn store(&self, data: EntryData) {
         let hash = data.hash;
         let data = data.compress();
@@ -103,30 +121,94 @@ impl EntryData {
     }
 }
 
+/// Tables are aligned to huge pages, so the kernel can back them with huge pages
+const HUGE_PAGE_SIZE: usize = 2 * 1024 * 1024;
+
+#[cfg(target_os = "linux")]
+const PROT_READ_WRITE: i32 = 0x1 | 0x2;
+#[cfg(target_os = "linux")]
+const MAP_PRIVATE_ANONYMOUS: i32 = 0x02 | 0x20;
+#[cfg(target_os = "linux")]
+const MADV_HUGEPAGE: i32 = 14;
+
+#[cfg(target_os = "linux")]
+extern "C" {
+    fn mmap(addr: *mut u8, len: usize, prot: i32, flags: i32, fd: i32, offset: i64) -> *mut u8;
+    fn munmap(addr: *mut u8, len: usize) -> i32;
+    fn madvise(addr: *mut u8, len: usize, advice: i32) -> i32;
+}
+
 pub struct TranspositionTable {
-    table: Vec<AtomicEntry>,
+    /// Zeroed by a clear when created, which is a valid empty entry
+    entries: NonNull<AtomicEntry>,
+    len: usize,
+    layout: Layout,
+}
+
+// The entries are atomics, and the allocation is owned by the table
+unsafe impl Send for TranspositionTable {}
+unsafe impl Sync for TranspositionTable {}
+
+impl Drop for TranspositionTable {
+    fn drop(&mut self) {
+        #[cfg(target_os = "linux")]
+        unsafe { munmap(self.entries.as_ptr() as *mut u8, self.layout.size()); }
+
+        #[cfg(not(target_os = "linux"))]
+        unsafe { alloc::dealloc(self.entries.as_ptr() as *mut u8, self.layout) }
+    }
 }
 
 impl TranspositionTable {
     const BYTES_PR_MB: usize = 1024 * 1024;
 
-    pub fn new(megabytes: usize) -> Self {
-        let bytes = Self::BYTES_PR_MB * megabytes;
+    /// Allocates the table without initializing it, and clears it split between this many threads,
+    /// so the memory is touched in parallel
+    pub fn new(megabytes: usize, threads: usize) -> Self {
+        let bytes = Self::BYTES_PR_MB * megabytes.max(1);
+        let len = bytes / size_of::<AtomicEntry>();
 
-        let entry_count = bytes / size_of::<AtomicEntry>();
-        
-        Self {
-            table: vec![(); entry_count].iter().map(|_| AtomicEntry::default()).collect()
+        let layout = Layout::from_size_align(len * size_of::<AtomicEntry>(), HUGE_PAGE_SIZE).expect("Invalid transposition table size");
+        let table = Self { entries: Self::allocate(layout), len, layout };
+        table.clear(threads);
+        table
+    }
+
+    /// Maps the pages without touching them, and advises the kernel to back them with transparent huge pages first.
+    /// Huge pages make the random accesses into the table much cheaper on the TLB. Failing to advise is harmless.
+    /// Recent kernels align mappings this large to huge pages
+    #[cfg(target_os = "linux")]
+    fn allocate(layout: Layout) -> NonNull<AtomicEntry> {
+        let addr = unsafe { mmap(std::ptr::null_mut(), layout.size(), PROT_READ_WRITE, MAP_PRIVATE_ANONYMOUS, -1, 0) };
+        if addr as usize == usize::MAX {
+            alloc::handle_alloc_error(layout)
         }
+
+        unsafe { madvise(addr, layout.size(), MADV_HUGEPAGE); }
+        NonNull::new(addr as *mut AtomicEntry).unwrap()
+    }
+
+    /// The memory is not zeroed here, as the table clears it in parallel
+    #[cfg(not(target_os = "linux"))]
+    fn allocate(layout: Layout) -> NonNull<AtomicEntry> {
+        let addr = unsafe { alloc::alloc(layout) };
+        let Some(entries) = NonNull::new(addr as *mut AtomicEntry) else {
+            alloc::handle_alloc_error(layout)
+        };
+        entries
+    }
+
+    fn table(&self) -> &[AtomicEntry] {
+        unsafe { slice::from_raw_parts(self.entries.as_ptr(), self.len) }
     }
 
     fn index(&self, hash: u64) -> usize {
-        hash as usize % self.table.len()
+        hash as usize % self.table().len()
     }
 
     /// Probe the transposition table for a hash. Returns None if no entry is found.
     pub fn probe(&self, hash: u64, ply: u8) -> Option<EntryData> {
-        let entry = self.table[self.index(hash)].load_valid(hash);
+        let entry = self.table()[self.index(hash)].load_valid(hash);
 
         match entry {
             Ok(mut entry) => {
@@ -143,6 +225,11 @@ impl TranspositionTable {
         }
     }
 
+    /// Whether the slot of the hash holds any entry. After a failed probe, this means it holds another position
+    pub fn is_occupied(&self, hash: u64) -> bool {
+        self.table()[self.index(hash)].raw() != (0, 0)
+    }
+
     pub fn record(&self, hash: u64, best_move: Move, depth: u8, score: i16, flag: HashFlag, ply: u8, generation: u8) {
         // Adjust mating scores here before storing
         let score = if score < -MATE_BOUND {
@@ -153,23 +240,134 @@ impl TranspositionTable {
             score
         };
 
-        let entry = EntryData::new(hash, score, depth, best_move, flag, generation);
-        
-        self.table[self.index(hash)].store(entry)
+        let slot = &self.table()[self.index(hash)];
+
+        // Deeper entries of other positions from the current search are kept, so the many shallow
+        // quiescence entries do not evict them. Entries of earlier searches are always replaced
+        let (old_hash, old_data) = slot.raw();
+        let old = EntryData::new_from_data(old_hash ^ old_data, old_data);
+        if old.hash != hash && old.generation == generation && old.depth > depth {
+            return
+        }
+
+        slot.store(EntryData::new(hash, score, depth, best_move, flag, generation))
     }
 
-    pub fn clear(&self) {
-        for entry in self.table.iter() {
-            entry.data.store(0, Relaxed);
-            entry.hash.store(0, Relaxed);
+    /// Clears the table, split between this many threads
+    pub fn clear(&self, threads: usize) {
+        let chunk_size = self.len.div_ceil(threads.max(1));
+
+        thread::scope(|scope| {
+            for chunk in self.table().chunks(chunk_size) {
+                scope.spawn(move || {
+                    for entry in chunk {
+                        entry.data.store(0, Relaxed);
+                        entry.hash.store(0, Relaxed);
+                    }
+                });
+            }
+        });
+    }
+
+    /// Writes the table to a file, along with the generation it was searched in.
+    ///
+    /// The header holds the format version, a fingerprint of the zobrist hashing and the table size,
+    /// so a file is only loaded into a table it is compatible with
+    pub fn save(&self, path: &str, generation: u8) -> Result<(), String> {
+        let write_err = |err| format!("Could not write hash file '{path}': {err}");
+        let mut writer = BufWriter::new(File::create(path).map_err(write_err)?);
+
+        let mut header = Vec::with_capacity(HASH_FILE_HEADER_SIZE);
+        header.extend_from_slice(HASH_FILE_MAGIC);
+        header.extend_from_slice(&HASH_FILE_VERSION.to_le_bytes());
+        header.extend_from_slice(&hash_fingerprint().to_le_bytes());
+        header.extend_from_slice(&(self.table().len() as u64).to_le_bytes());
+        header.push(generation);
+        writer.write_all(&header).map_err(write_err)?;
+
+        for entry in self.table().iter() {
+            let (hash, data) = entry.raw();
+            writer.write_all(&hash.to_le_bytes()).map_err(write_err)?;
+            writer.write_all(&data.to_le_bytes()).map_err(write_err)?;
+        }
+
+        writer.flush().map_err(write_err)
+    }
+
+    /// The size in MB of the table a hash file was saved from. The Hash option must be set to it to load the file
+    pub fn file_megabytes(path: &str) -> Result<usize, String> {
+        let (mut reader, file_len) = Self::open_hash_file(path)?;
+        let (entry_count, _) = Self::read_header(&mut reader, file_len, path)?;
+        Ok(entry_count as usize * size_of::<AtomicEntry>() / Self::BYTES_PR_MB)
+    }
+
+    /// Replaces the content of the table with a file written by save, and returns the generation it was searched in.
+    /// The table is left untouched if the file does not fit it
+    pub fn load(&self, path: &str) -> Result<u8, String> {
+        let (mut reader, file_len) = Self::open_hash_file(path)?;
+        let (entry_count, generation) = Self::read_header(&mut reader, file_len, path)?;
+
+        if entry_count != self.table().len() as u64 {
+            let megabytes = entry_count as usize * size_of::<AtomicEntry>() / Self::BYTES_PR_MB;
+            return Err(format!("The hash file is for a {megabytes} MB table. Set the Hash option to {megabytes} before loading it"))
+        }
+
+        let read_err = |err| format!("Could not read hash file '{path}': {err}");
+        let mut words = [0; 16];
+        for entry in self.table().iter() {
+            reader.read_exact(&mut words).map_err(read_err)?;
+            let hash = u64::from_le_bytes(words[0..8].try_into().unwrap());
+            let data = u64::from_le_bytes(words[8..16].try_into().unwrap());
+            entry.store_raw(hash, data);
+        }
+
+        Ok(generation)
+    }
+
+    fn open_hash_file(path: &str) -> Result<(BufReader<File>, u64), String> {
+        let read_err = |err| format!("Could not read hash file '{path}': {err}");
+        let file = File::open(path).map_err(read_err)?;
+        let file_len = file.metadata().map_err(read_err)?.len();
+        Ok((BufReader::new(file), file_len))
+    }
+
+    /// Validates the header of a hash file against the file length and the zobrist keys, and returns the
+    /// entry count and generation
+    fn read_header(reader: &mut BufReader<File>, file_len: u64, path: &str) -> Result<(u64, u8), String> {
+        let read_err = |err| format!("Could not read hash file '{path}': {err}");
+        let mut header = [0; HASH_FILE_HEADER_SIZE];
+        if file_len < HASH_FILE_HEADER_SIZE as u64 {
+            return Err("Not a hash file. The file is too short".to_string())
+        }
+        reader.read_exact(&mut header).map_err(read_err)?;
+
+        let word = |offset: usize| u64::from_le_bytes(header[offset..offset + 8].try_into().unwrap());
+        if &header[0..8] != HASH_FILE_MAGIC {
+            return Err("Not a hash file".to_string())
+        }
+
+        let version = u32::from_le_bytes(header[8..12].try_into().unwrap());
+        if version != HASH_FILE_VERSION {
+            return Err(format!("Unsupported hash file version {version}. Expected version {HASH_FILE_VERSION}"))
         }
+
+        if word(12) != hash_fingerprint() {
+            return Err("The hash file was written with different zobrist keys, and cannot be used".to_string())
+        }
+
+        let entry_count = word(20);
+        if file_len != HASH_FILE_HEADER_SIZE as u64 + entry_count * 16 {
+            return Err("The hash file is truncated or corrupt".to_string())
+        }
+
+        Ok((entry_count, header[28]))
     }
 
     /// Probes first 1000 buckets to estimate fill rate (0 = 0%, 1 = 100%)
     pub fn fill_rate(&self) -> f32 {
         let mut checked = 0;
         let mut found = 0;
-        for entry in self.table.iter().take(1000) {
+        for entry in self.table().iter().take(1000) {
             let entry = entry.load_unsafe();
             if entry.hash != 0 {
                 found += 1;
@@ -179,4 +377,10 @@ impl TranspositionTable {
 
         return found as f32 / checked as f32;
     }
+}
+
+/// Identifies the zobrist keys, so a hash file is not used with keys it was not written with
+fn hash_fingerprint() -> u64 {
+    let kiwipete = Position::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -").unwrap();
+    Position::start_pos().zobrist_hash ^ kiwipete.zobrist_hash.rotate_left(32)
 }
\ No newline at end of file
@@ -1,14 +1,36 @@
+use super::*;
+
+pub const DEFAULT_MOVE_OVERHEAD: u128 = 10;
+pub const MAX_CONTEMPT: i16 = 100;
+pub const MAX_HASH_MB: usize = 1048576;
+
 #[derive(Copy, Clone)]
 pub struct Settings {
     pub threads: u8,
     pub transposition_table_mb: usize,
+    /// Milliseconds subtracted from the available time, to account for communication delays
+    pub move_overhead: u128,
+    /// Centipawns the engine considers a draw worse than equal for itself. Negative values make it seek draws
+    pub contempt: i16,
+    /// Play at the strength of elo, instead of the skill level
+    pub limit_strength: bool,
+    pub elo: u16,
+    /// 0 to MAX_SKILL_LEVEL, where MAX_SKILL_LEVEL is full strength
+    pub skill_level: u8,
+    pub search_params: SearchParams,
 }
 
 impl Default for Settings {
     fn default() -> Self {
         Self {
             threads: 1,
-            transposition_table_mb: 16
+            transposition_table_mb: 16,
+            move_overhead: DEFAULT_MOVE_OVERHEAD,
+            contempt: 0,
+            limit_strength: false,
+            elo: DEFAULT_ELO,
+            skill_level: MAX_SKILL_LEVEL,
+            search_params: SearchParams::default(),
         }
     }
 }
@@ -23,4 +45,34 @@ impl Settings {
         self.transposition_table_mb = transposition_table_mb;
         self
     }
+
+    pub fn move_overhead(mut self, move_overhead: u128) -> Self {
+        self.move_overhead = move_overhead;
+        self
+    }
+
+    pub fn contempt(mut self, contempt: i16) -> Self {
+        self.contempt = contempt;
+        self
+    }
+
+    pub fn limit_strength(mut self, limit_strength: bool) -> Self {
+        self.limit_strength = limit_strength;
+        self
+    }
+
+    pub fn elo(mut self, elo: u16) -> Self {
+        self.elo = elo;
+        self
+    }
+
+    pub fn skill_level(mut self, skill_level: u8) -> Self {
+        self.skill_level = skill_level;
+        self
+    }
+
+    pub fn search_params(mut self, search_params: SearchParams) -> Self {
+        self.search_params = search_params;
+        self
+    }
 }
\ No newline at end of file
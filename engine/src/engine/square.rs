@@ -1,6 +1,6 @@
 use std::{fmt::Display, mem};
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 #[allow(non_camel_case_types)]
 #[allow(dead_code)]
 #[repr(u8)]
@@ -38,12 +38,12 @@ impl Square {
             return Err(format!("Square string must be 2 chars but was {}", chars.len()))
         }
         
-        let file = chars[0] - 97;
+        let file = chars[0].wrapping_sub(b'a');
         if file > 7 {
             return Err(format!("Illegal file in square string"))
         }
 
-        let rank = 8 - (chars[1] as char).to_digit(10).unwrap() as u8;
+        let rank = b'8'.wrapping_sub(chars[1]);
         if rank > 7 {
             return Err(format!("Illegal rank in square string"))
         }
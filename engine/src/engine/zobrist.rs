@@ -6,7 +6,7 @@ macro_rules! generate_keys {
     ($count: expr, $seed: expr) =>  {
         {
             let count: usize = $count;
-            let mut seed: u32 = $seed;
+            let mut seed: u64 = $seed;
             let mut keys = [0; $count];
             const_for!(i in 0..count => {
                 let res = rand_u64(seed);
@@ -35,7 +35,7 @@ impl Position {
     }
 
     pub fn apply_piece_zobrist(&mut self, color: Color, piece_type: PieceType, square: u8) {
-        self.zobrist_hash ^= PIECE_KEYS[piece_type.index(color) * 12 + square as usize]
+        self.zobrist_hash ^= PIECE_KEYS[piece_type.index(color) * 64 + square as usize]
     }
     
     pub fn apply_enpassant_zobrist(&mut self, square: u8) {
@@ -69,26 +69,15 @@ impl Position {
     }
 }
 
-const fn rand_u32(state: u32) -> u32 {
-    let mut num: u64 = state as u64;
+/// Splitmix64. The keys must not be linear combinations of each other, or different positions get the same hash
+const fn rand_u64(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
 
-    // XOR shift algorithm
-    const_for!(_ in 0..27 => {
-        num ^= num << 13;
-        num ^= num >> 17;
-        num ^= num << 5;
-    });
+    let mut num = state;
+    num = (num ^ (num >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    num = (num ^ (num >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    num ^= num >> 31;
 
-    // return random number
-    return num as u32;
-}
-
-// generate 64-bit pseudo random numbers
-const fn rand_u64(state: u32) -> (u64, u32) {
-    let n1 = rand_u32(state);
-    let n2 = rand_u32(n1);
-    let n3 = rand_u32(n2);
-    
-    // return random number
-    return (n1 as u64 | ((n2 as u64) << 32), n3);
+    // return random number and the next state
+    (num, state)
 }
\ No newline at end of file
@@ -0,0 +1,154 @@
+use std::{fs, time::Instant};
+
+use crate::engine::*;
+
+/// Milliseconds pr. position when no limit is given
+pub const DEFAULT_EPD_MOVETIME: u128 = 1000;
+
+/// A test position from an EPD file. Moves are kept in UCI notation
+pub struct EpdPosition {
+    pub pos: Position,
+    pub id: String,
+    /// Solved if one of these is played
+    pub best_moves: Vec<String>,
+    /// Solved if none of these are played
+    pub avoid_moves: Vec<String>,
+    /// STS style points for moves, from the 'c0' opcode
+    pub points: Vec<(String, u32)>,
+}
+
+impl EpdPosition {
+    /// Parses the position and the 'bm', 'am', 'id' and 'c0' opcodes of an EPD line. Other opcodes are ignored
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let fields: Vec<&str> = line.split_whitespace().take(4).collect();
+        if fields.len() < 4 {
+            return Err("Expected board, color, castling and en passant fields".to_string())
+        }
+        let pos = Position::from_fen(&fields.join(" "))?;
+
+        // The opcodes start after the 4th field
+        let mut rest = line.trim_start();
+        for _ in 0..4 {
+            rest = rest.split_once(char::is_whitespace).map_or("", |(_, rest)| rest.trim_start());
+        }
+
+        let mut epd = Self { pos, id: String::new(), best_moves: Vec::new(), avoid_moves: Vec::new(), points: Vec::new() };
+
+        for operation in split_operations(rest) {
+            let (opcode, operands) = operation.split_once(char::is_whitespace).unwrap_or((operation, ""));
+            let operands = operands.trim().trim_matches('"');
+
+            match opcode {
+                "bm" => epd.best_moves = parse_moves(&pos, operands)?,
+                "am" => epd.avoid_moves = parse_moves(&pos, operands)?,
+                "id" => epd.id = operands.to_string(),
+                // Only STS suites use c0 for points. Other comments are ignored
+                "c0" => epd.points = parse_points(&pos, operands).unwrap_or_default(),
+                _ => (),
+            }
+        }
+
+        if epd.best_moves.is_empty() && epd.avoid_moves.is_empty() {
+            return Err("Position has neither a 'bm' nor an 'am' opcode".to_string())
+        }
+
+        Ok(epd)
+    }
+
+    pub fn is_solved(&self, moove: &str) -> bool {
+        (self.best_moves.is_empty() || self.best_moves.iter().any(|m| m == moove))
+            && !self.avoid_moves.iter().any(|m| m == moove)
+    }
+
+    pub fn points(&self, moove: &str) -> u32 {
+        self.points.iter().find(|(m, _)| m == moove).map_or(0, |(_, points)| *points)
+    }
+}
+
+/// Splits the operations on semicolons outside of quotes
+fn split_operations(opcodes: &str) -> Vec<&str> {
+    let mut operations = Vec::new();
+    let mut in_quotes = false;
+    let mut start = 0;
+    for (i, char) in opcodes.char_indices() {
+        match char {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => {
+                operations.push(opcodes[start..i].trim());
+                start = i + 1;
+            },
+            _ => (),
+        }
+    }
+    operations.push(opcodes[start..].trim());
+    operations.retain(|op| !op.is_empty());
+    operations
+}
+
+fn parse_moves(pos: &Position, san_moves: &str) -> Result<Vec<String>, String> {
+    san_moves.split_whitespace().map(|san| pos.parse_san(san).map(|m| m.to_string())).collect()
+}
+
+/// Parses STS points like "Nf3=10, Qe2=5, d4=3"
+fn parse_points(pos: &Position, points: &str) -> Option<Vec<(String, u32)>> {
+    points.split(',').map(|entry| {
+        let (san, points) = entry.trim().split_once('=')?;
+        Some((pos.parse_san(san).ok()?.to_string(), points.parse().ok()?))
+    }).collect()
+}
+
+/// Searches every position of an EPD test suite, and reports the solved positions, time to solution and STS points.
+/// Each position is searched with the given limits, from a clear hash table. The search is single-threaded, as the move
+/// picked by the thread vote could differ from the move the time to solution is measured for
+pub fn run_epd<E: Evaluator + Send + Clone + 'static>(path: &str, args: SearchArgs, settings: Settings, evaluator: E) -> Result<(), String> {
+    let content = fs::read_to_string(path).map_err(|err| format!("Could not read EPD file '{path}': {err}"))?;
+
+    let mut positions = Vec::new();
+    for (line_number, line) in content.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let epd = EpdPosition::parse(line).map_err(|err| format!("Line {}: {err}", line_number + 1))?;
+        positions.push(epd);
+    }
+
+    let search = Search::new(settings.threads(1));
+    let before = Instant::now();
+
+    let mut solved = 0;
+    let mut solution_time = 0;
+    let mut points = 0;
+    let mut max_points = 0;
+
+    println!();
+    for (i, epd) in positions.iter().enumerate() {
+        search.clear_hash();
+        search.pool.clear();
+
+        let result = search.start(epd.pos, args, false, evaluator.clone());
+        let (moove, san) = match result.best_move {
+            Some(m) => (m.to_string(), epd.pos.san(m)),
+            None => (String::new(), "none".to_string()),
+        };
+
+        let is_solved = epd.is_solved(&moove);
+        if is_solved {
+            solved += 1;
+            solution_time += result.best_move_time;
+        }
+        points += epd.points(&moove);
+        max_points += epd.points.iter().map(|(_, p)| *p).max().unwrap_or(0);
+
+        let time = if is_solved { format!("{} ms", result.best_move_time) } else { "-".to_string() };
+        println!(" {:>4} {:<24} {:<8} {:<9} {:>10}", i + 1, epd.id, san, if is_solved { "solved" } else { "unsolved" }, time);
+    }
+
+    println!();
+    println!(" Solved {solved} of {} positions in {} ms", positions.len(), before.elapsed().as_millis());
+    if solved > 0 {
+        println!(" Average time to solution: {} ms", solution_time / solved as u128);
+    }
+    if max_points > 0 {
+        println!(" STS points: {points} of {max_points}");
+    }
+    println!();
+
+    Ok(())
+}
@@ -12,6 +12,9 @@ pub fn interface_loop() {
 
     let mut current_search: Search = Search::new(settings);
 
+    // Evaluator loaded from a weights file. The compiled weights are used if none is loaded
+    let mut evaluator: Option<HeapEvaluator> = None;
+
     loop {
         let line = wait_for_input(&ui_receiver);
         let mut command = line.as_str().trim();
@@ -29,9 +32,24 @@ pub fn interface_loop() {
                 println!();
 
                 // Advertise options
-                println!("option name Hash type spin default 16 min 1 max 1048576");
+                println!("option name Hash type spin default 16 min 1 max {MAX_HASH_MB}");
                 println!("option name Threads type spin default 1 min 1 max 255");
                 println!("option name Clear Hash type button");
+                println!("option name Move Overhead type spin default {DEFAULT_MOVE_OVERHEAD} min 0 max 5000");
+                println!("option name Contempt type spin default 0 min -{MAX_CONTEMPT} max {MAX_CONTEMPT}");
+                println!("option name EvalFile type string default <empty>");
+                println!("option name UCI_LimitStrength type check default false");
+                println!("option name UCI_Elo type spin default {DEFAULT_ELO} min {MIN_ELO} max {MAX_ELO}");
+                println!("option name Skill Level type spin default {MAX_SKILL_LEVEL} min 0 max {MAX_SKILL_LEVEL}");
+
+                #[cfg(feature = "tune")]
+                for (name, min, max) in SearchParams::SPECS {
+                    println!("option name {name} type spin default {} min {min} max {max}", settings.search_params.get(name).unwrap());
+                }
+                #[cfg(feature = "tune")]
+                for name in SearchParams::TOGGLES {
+                    println!("option name {name} type check default {}", settings.search_params.get_toggle(name).unwrap());
+                }
 
                 // Apply modified settings
                 current_search = Search::new(settings);
@@ -45,14 +63,27 @@ pub fn interface_loop() {
                 }
 
                 if command == "name Clear Hash" {
-                    current_search.tt.clear();
+                    current_search.clear_hash();
+                    continue;
+                }
+
+                if let Some(path) = command.strip_prefix("name EvalFile value") {
+                    match path.trim() {
+                        "" | "<empty>" => evaluator = None,
+                        path => match HeapEvaluator::load(path) {
+                            Ok(e) => evaluator = Some(e),
+                            Err(err) => println!("{err}"),
+                        }
+                    }
                     continue;
                 }
 
                 match parse_set_option(&mut command, settings) {
                     Ok(n_settings) => {
                         settings = n_settings;
-                        current_search.update_settings(settings)
+                        if let Err(err) = current_search.update_settings(settings) {
+                            println!("{err}")
+                        }
                     },
                     Err(err) => println!("{err}"),
                 }
@@ -64,7 +95,8 @@ pub fn interface_loop() {
                 if current_search.is_running() {
                     println!("Cannot start a new game while a search is running");
                 }
-                current_search.tt.clear()
+                current_search.clear_hash();
+                current_search.pool.clear();
             },
             "position" => {
                 match parse_position(&mut command) {
@@ -78,6 +110,13 @@ pub fn interface_loop() {
                     continue
                 }
 
+                // 'go perft [depth]' is an extension to UCI, doing the same as the perft command
+                if command.starts_with("perft") {
+                    take_next(&mut command);
+                    parse_perft(&mut command, &pos, current_search.settings());
+                    continue
+                }
+
                 let meta = match parse_go(&mut command, pos) {
                     Ok(c) => c,
                     Err(err) => {
@@ -87,8 +126,12 @@ pub fn interface_loop() {
                 };
 
                 let search = current_search.clone();
+                let evaluator = evaluator.clone();
                 thread::spawn(move || {
-                    search.start(pos, meta, true, CONST_EVALUATOR);
+                    match evaluator {
+                        Some(e) => search.start(pos, meta, true, e),
+                        None => search.start(pos, meta, true, CONST_EVALUATOR),
+                    };
                 });
             },
             "stop" => {
@@ -137,7 +180,12 @@ pub fn interface_loop() {
                 }
             },
             "eval" => {
-                println!("Heuristic value: {}", pos.evaluate(CONST_EVALUATOR))
+                let trace = match &evaluator {
+                    Some(e) => pos.trace(e),
+                    None => pos.trace(&CONST_EVALUATOR),
+                };
+                println!("\n{trace}\n");
+                println!("Heuristic value: {}", if pos.active_color.is_white() { trace.eval } else { -trace.eval })
             },
             "threefold" => {
                 println!("{}", pos.rep_table.is_in_3_fold_rep(&pos))
@@ -149,13 +197,46 @@ pub fn interface_loop() {
                 println!("Zobrist hash:: {:x}", pos.zobrist_hash)
             }
             "perft" => {
-                parse_perft(&mut command, &pos);
+                parse_perft(&mut command, &pos, current_search.settings());
             },
             "bench" => {
                 match take_next(&mut command) {
-                    Some("save") => run_bench(true),
-                    None => run_bench(false),
-                    Some(arg) => println!("Illegal parameter for bench '{arg}'. Only 'save' is supported"),
+                    Some("full") => run_bench(take_next(&mut command) == Some("save")),
+                    Some("skill") => run_skill_bench(),
+                    Some("json") => match parse_bench_depth(take_next(&mut command)) {
+                        Ok(depth) => run_json_bench(depth),
+                        Err(err) => println!("{err}"),
+                    },
+                    None => run_node_bench(BASE_DEPTH),
+                    Some(arg) => match parse_bench_depth(Some(arg)) {
+                        Ok(depth) => run_node_bench(depth),
+                        Err(_) => println!("Illegal parameter for bench '{arg}'. Expected a depth, 'full', 'json' or 'skill'"),
+                    },
+                }
+            },
+            "epd" => {
+                let path = match take_next(&mut command) {
+                    Some(path) => path.to_string(),
+                    None => {
+                        println!("Provide an EPD file to run");
+                        continue
+                    }
+                };
+
+                let args = match parse_epd_limits(&mut command) {
+                    Ok(args) => args,
+                    Err(err) => {
+                        println!("{err}");
+                        continue
+                    }
+                };
+
+                let result = match &evaluator {
+                    Some(e) => run_epd(&path, args, settings, e.clone()),
+                    None => run_epd(&path, args, settings, CONST_EVALUATOR),
+                };
+                if let Err(err) = result {
+                    println!("{err}")
                 }
             },
             "legal" => {
@@ -164,7 +245,33 @@ pub fn interface_loop() {
                 }
             },
             "cleartt" => {
-                current_search.tt.clear();
+                current_search.clear_hash();
+            },
+            "savehash" => {
+                if current_search.is_running() {
+                    println!("Cannot save the hash table while a search is running");
+                    continue;
+                }
+
+                match command {
+                    "" => println!("Provide a file to save the hash table to"),
+                    path => if let Err(err) = current_search.save_hash(path) {
+                        println!("{err}")
+                    },
+                }
+            },
+            "loadhash" => {
+                if current_search.is_running() {
+                    println!("Cannot load a hash table while a search is running");
+                    continue;
+                }
+
+                match command {
+                    "" => println!("Provide a hash file to load"),
+                    path => if let Err(err) = current_search.load_hash(path) {
+                        println!("{err}")
+                    },
+                }
             },
             "fillrate" => {
                 println!("Fill rate: {:.2}%", current_search.tt.fill_rate() * 100.0)
@@ -184,8 +291,8 @@ fn parse_set_option(mut command: &str, mut settings: Settings) -> Result<Setting
         Some("Hash") => match take_next(&mut command) {
             Some("value") => match take_next_num(&mut command) {
                 Some(megabytes) => {
-                    if (megabytes & (megabytes - 1)) != 0 || megabytes < 1 {
-                        return Err("Transposition size must be a positive power of 2".to_string());
+                    if !(1..=MAX_HASH_MB).contains(&megabytes) {
+                        return Err(format!("Hash must be between 1 and {MAX_HASH_MB} MB"));
                     }
                     settings.transposition_table_mb = megabytes;
                 },
@@ -205,6 +312,71 @@ fn parse_set_option(mut command: &str, mut settings: Settings) -> Result<Setting
             },
             _ => return Err("Expected 'value' after 'Threads'".to_string())
         },
+        Some("Move") => match take_next(&mut command) {
+            Some("Overhead") => match take_next(&mut command) {
+                Some("value") => match take_next_num(&mut command) {
+                    Some(overhead) if overhead <= 5000 => settings.move_overhead = overhead,
+                    _ => return Err("Move Overhead must be between 0 and 5000".to_string())
+                },
+                _ => return Err("Expected 'value' after 'Move Overhead'".to_string())
+            },
+            _ => return Err("Unknown option name 'Move'".to_string())
+        },
+        Some("Contempt") => match take_next(&mut command) {
+            Some("value") => match take_next_num(&mut command) {
+                Some(contempt) if (-MAX_CONTEMPT..=MAX_CONTEMPT).contains(&contempt) => settings.contempt = contempt,
+                _ => return Err(format!("Contempt must be between -{MAX_CONTEMPT} and {MAX_CONTEMPT}"))
+            },
+            _ => return Err("Expected 'value' after 'Contempt'".to_string())
+        },
+        Some("UCI_LimitStrength") => match take_next(&mut command) {
+            Some("value") => match take_next(&mut command) {
+                Some("true") => settings.limit_strength = true,
+                Some("false") => settings.limit_strength = false,
+                _ => return Err("Value for UCI_LimitStrength option must be true or false".to_string())
+            },
+            _ => return Err("Expected 'value' after 'UCI_LimitStrength'".to_string())
+        },
+        Some("UCI_Elo") => match take_next(&mut command) {
+            Some("value") => match take_next_num(&mut command) {
+                Some(elo) if (MIN_ELO..=MAX_ELO).contains(&elo) => settings.elo = elo,
+                _ => return Err(format!("UCI_Elo must be between {MIN_ELO} and {MAX_ELO}"))
+            },
+            _ => return Err("Expected 'value' after 'UCI_Elo'".to_string())
+        },
+        Some("Skill") => match take_next(&mut command) {
+            Some("Level") => match take_next(&mut command) {
+                Some("value") => match take_next_num(&mut command) {
+                    Some(level) if level <= MAX_SKILL_LEVEL => settings.skill_level = level,
+                    _ => return Err(format!("Skill Level must be between 0 and {MAX_SKILL_LEVEL}"))
+                },
+                _ => return Err("Expected 'value' after 'Skill Level'".to_string())
+            },
+            _ => return Err("Unknown option name 'Skill'".to_string())
+        },
+        #[cfg(feature = "tune")]
+        Some(name) if settings.search_params.get(name).is_some() => {
+            let name = name.to_string();
+            match take_next(&mut command) {
+                Some("value") => match take_next_num(&mut command) {
+                    Some(value) => settings.search_params.set(&name, value)?,
+                    _ => return Err(format!("No value provided for {name} option"))
+                },
+                _ => return Err(format!("Expected 'value' after '{name}'"))
+            }
+        },
+        #[cfg(feature = "tune")]
+        Some(name) if settings.search_params.get_toggle(name).is_some() => {
+            let name = name.to_string();
+            match take_next(&mut command) {
+                Some("value") => match take_next(&mut command) {
+                    Some("true") => settings.search_params.set_toggle(&name, true)?,
+                    Some("false") => settings.search_params.set_toggle(&name, false)?,
+                    _ => return Err(format!("Value for {name} option must be true or false"))
+                },
+                _ => return Err(format!("Expected 'value' after '{name}'"))
+            }
+        },
         Some(unknown) => return Err(format!("Unknown option name '{unknown}'")),
         None => return Err("No option name provided".to_string()),
     }
@@ -276,7 +448,7 @@ fn parse_position(command: &mut &str) -> Result<Position, String> {
 
     let mut pos = match take_next(&mut pos_str) {
         Some("startpos") => Position::start_pos(),
-        Some("fen") => Position::from_fen(pos_str)?,
+        Some("fen") => Position::from_fen_strict(pos_str)?,
         _ => return Err(format!("Illegal position argument"))
     };
 
@@ -378,15 +550,41 @@ fn parse_go(command: &mut &str, pos: Position) -> Result<SearchArgs, String> {
     SearchArgs::new(max_depth, ponder, infinite, time, inc, movestogo, nodes, movetime)
 }
 
-fn parse_perft(command: &mut &str, pos: &Position) {
+/// Parses the search limit of the epd command. The default is 1 second pr. position
+fn parse_epd_limits(command: &mut &str) -> Result<SearchArgs, String> {
+    let (mut depth, mut nodes, mut movetime) = (None, None, None);
+
+    match take_next(command) {
+        Some("depth") => depth = Some(take_next_num(command).ok_or("Illegal epd depth")?),
+        Some("nodes") => nodes = Some(take_next_num(command).ok_or("Illegal epd nodes")?),
+        Some("movetime") => movetime = Some(take_next_num(command).ok_or("Illegal epd movetime")?),
+        None => movetime = Some(DEFAULT_EPD_MOVETIME),
+        Some(arg) => return Err(format!("Illegal epd limit '{arg}'. Expected 'depth', 'nodes' or 'movetime'")),
+    }
+
+    SearchArgs::new(depth, false, false, None, None, None, nodes, movetime)
+}
+
+/// Runs perft on the configured number of threads, with a perft hash table the size of the Hash option
+fn parse_perft(command: &mut &str, pos: &Position, settings: Settings) {
     let depth = match take_next_num(command) {
-        Some(d) => d,
-        None => {
+        Some(d) if d > 0 => d,
+        _ => {
             println!("Illegal perft command");
             return
         },
     };
 
     let before = Instant::now();
-    println!("\n Found: {} moves at depth {depth} in {}ms\n", pos.perft::<true>(depth), before.elapsed().as_millis())
+    let table = PerftTable::new(settings.transposition_table_mb);
+    let mut divide = pos.perft_divide(depth, settings.threads as usize, Some(&table));
+    divide.sort_by_key(|(moove, _)| moove.to_string());
+
+    for (moove, nodes) in &divide {
+        println!("{moove}: {nodes}");
+    }
+
+    let nodes: u64 = divide.iter().map(|(_, nodes)| nodes).sum();
+    let millis = before.elapsed().as_millis();
+    println!("\n Found: {nodes} moves at depth {depth} in {millis}ms ({:.2} MNodes/s)\n", nodes as f64 / millis.max(1) as f64 / 1000.)
 }
\ No newline at end of file
@@ -5,7 +5,9 @@ pub const PKG_AUTHORS: &str = env!("CARGO_PKG_AUTHORS");
 mod engine;
 mod interface;
 mod bench;
+mod epd;
 
 pub use crate::engine::*;
 pub use interface::*;
-pub use bench::*;
\ No newline at end of file
+pub use bench::*;
+pub use epd::*;
\ No newline at end of file
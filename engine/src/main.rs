@@ -5,11 +5,26 @@ use cadabra::*;
 fn main() {    
     let args: Vec<String> = env::args().collect();
 
-    println!("{} {} by {}", PKG_NAME, PKG_VERSION, PKG_AUTHORS);
+    // JSON output is read by tools, so nothing else is printed
+    let json = args.contains(&"json".to_string());
+    if !json {
+        println!("{} {} by {}", PKG_NAME, PKG_VERSION, PKG_AUTHORS);
+    }
 
     // Benchmarking
     if args.contains(&"bench".to_string()) {
-        run_bench(args.contains(&"save".to_string()));
+        if args.contains(&"full".to_string()) {
+            run_bench(args.contains(&"save".to_string()));
+            return;
+        }
+
+        // The node bench takes an optional base depth after 'bench'
+        let depth_arg = args.iter().skip_while(|arg| *arg != "bench").skip(1).find(|arg| *arg != "json");
+        match parse_bench_depth(depth_arg.map(|arg| arg.as_str())) {
+            Ok(depth) if json => run_json_bench(depth),
+            Ok(depth) => run_node_bench(depth),
+            Err(err) => println!("{err}"),
+        }
         return;
     }
     
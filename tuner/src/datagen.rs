@@ -0,0 +1,106 @@
+use std::{path::PathBuf, fs::File, io::{LineWriter, Write}, sync::{atomic::{AtomicUsize, Ordering::Relaxed}, Mutex}, time::Instant};
+use cadabra::*;
+use rand::{thread_rng, seq::SliceRandom};
+use rayon::prelude::*;
+
+pub const DEFAULT_GAMES: usize = 1000;
+pub const DEFAULT_DEPTH: u8 = 6;
+/// Default number of random moves played before the engine takes over
+pub const DEFAULT_RANDOM_PLIES: usize = 8;
+
+/// Games longer than this are adjudicated as draws
+const MAX_GAME_PLIES: usize = 400;
+const HASH_MB: usize = 16;
+
+pub struct DatagenOptions {
+    pub output: PathBuf,
+    pub games: usize,
+    pub depth: u8,
+    pub random_plies: usize,
+}
+
+impl DatagenOptions {
+    pub fn new(output: PathBuf) -> Self {
+        Self {
+            output,
+            games: DEFAULT_GAMES,
+            depth: DEFAULT_DEPTH,
+            random_plies: DEFAULT_RANDOM_PLIES,
+        }
+    }
+}
+
+/// Generates a fen file for texel tuning by letting the engine play against itself.
+///
+/// Excludes the random opening, positions in check, and positions where the best move is tactical.
+pub fn generate(options: DatagenOptions) {
+    println!("Generating {} games at depth {}...", options.games, options.depth);
+
+    let before = Instant::now();
+    let finished = AtomicUsize::new(0);
+    let lines = Mutex::new(Vec::new());
+
+    (0..options.games).into_par_iter().for_each(|_| {
+        let mut game_lines = play_game(&options);
+        lines.lock().unwrap().append(&mut game_lines);
+
+        let finished = finished.fetch_add(1, Relaxed) + 1;
+        if finished % 10 == 0 || finished == options.games {
+            print!("\r{finished}/{} games", options.games);
+            std::io::stdout().flush().unwrap();
+        }
+    });
+    println!();
+
+    let mut lines = lines.into_inner().unwrap();
+    lines.shuffle(&mut thread_rng());
+
+    let mut writer = LineWriter::new(File::create(&options.output).expect("Unable to create file"));
+    writer.write_all(lines.join("\n").as_bytes()).unwrap();
+    println!("Wrote {} fens to '{}' in {}", lines.len(), options.output.display(), pretty_duration::pretty_duration(&before.elapsed(), None));
+}
+
+/// Plays a single game and returns the recorded positions labeled with the result
+fn play_game(options: &DatagenOptions) -> Vec<String> {
+    let search = Search::new(Settings::default().threads(1).transposition_table_mb(HASH_MB));
+    let mut pos = Position::start_pos();
+    let mut fens = Vec::new();
+    let mut plies = 0;
+
+    // Random opening. Start over if it ends the game
+    for _ in 0..options.random_plies {
+        let moves: Vec<_> = pos.generate_moves().collect();
+        match moves.choose(&mut thread_rng()) {
+            Some(m) => pos.make_move(*m),
+            None => return play_game(options),
+        }
+    }
+
+    let result = loop {
+        if pos.generate_moves().len() == 0 {
+            break match (pos.is_in_check(), pos.active_color.is_white()) {
+                (true, true) => "0",
+                (true, false) => "1",
+                (false, _) => "½",
+            }
+        }
+
+        if pos.half_moves >= 100 || pos.rep_table.is_in_3_fold_rep(&pos) || pos.is_insufficient_material() || plies >= MAX_GAME_PLIES {
+            break "½"
+        }
+
+        let best_move = match search.start(pos, SearchArgs::new_simple_depth(options.depth), false, CONST_EVALUATOR).best_move {
+            Some(m) => m,
+            None => break "½",
+        };
+
+        if !pos.is_in_check() && !best_move.is_capture() && !best_move.is_promotion() {
+            fens.push(pos.fen_string());
+        }
+
+        pos.make_move(best_move);
+        plies += 1;
+    };
+
+    fens.into_iter().map(|fen| format!("[{result}]{fen}")).collect()
+}
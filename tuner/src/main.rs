@@ -1,9 +1,128 @@
-//mod texel_tuner;
+mod texel_tuner;
 mod pgn_to_fen;
 mod tuner_evaluator;
 mod evolution_tuner;
+mod datagen;
+mod spsa;
+
+use std::{path::PathBuf, str::FromStr};
+
+use pgn_to_fen::*;
+use texel_tuner::TexelOptions;
+use datagen::DatagenOptions;
+use spsa::SpsaOptions;
+
+const USAGE: &str = "Usage: tuner <command> [options]
+
+Commands:
+    texel <data>          Texel tune the evaluation on a .fen or .pgn file
+        --k <k>               Use this K instead of searching for the best one
+        --lr <rate>           Learning rate of the optimizer. Default: 1
+        --iterations <n>      Maximum number of iterations. Default: 10000
+        --split <fraction>    Fraction of the positions used for validation. Default: 0.1
+        --resume <file>       Continue from a results file in 'tuning_results'
+
+    pgn2fen <pgn>         Convert a .pgn file to a .fen file usable for texel tuning
+
+    evolve                Run the evolution tuner
+
+    datagen <output>      Generate a .fen file from self-play games
+        --games <n>           Number of games to play. Default: 1000
+        --depth <d>           Search depth per move. Default: 6
+        --random <n>          Random plies at the start of each game. Default: 8
+
+    spsa                  Tune the search parameters with SPSA over self-play games
+        --iterations <n>      Number of iterations. Default: 1000
+        --pairs <n>           Game pairs pr. iteration. Default: 8
+        --nodes <n>           Node limit per move. Default: 20000";
 
 #[tokio::main]
 async fn main() {
-    evolution_tuner::tune().await;
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match args.first().map(|a| a.as_str()) {
+        Some("texel") => parse_texel(&args[1..]).map(texel_tuner::tune),
+        Some("pgn2fen") => match args.get(1) {
+            Some(pgn) => {
+                println!("Wrote '{}'", generate_fen_from_pgn(PathBuf::from(pgn)).display());
+                Ok(())
+            },
+            None => Err("No pgn file provided".to_string()),
+        },
+        Some("evolve") => {
+            evolution_tuner::tune().await;
+            Ok(())
+        },
+        Some("datagen") => parse_datagen(&args[1..]).map(datagen::generate),
+        Some("spsa") => parse_spsa(&args[1..]).map(spsa::tune),
+        Some("help") | None => {
+            println!("{USAGE}");
+            Ok(())
+        },
+        Some(unknown) => Err(format!("Unknown command '{unknown}'")),
+    };
+
+    if let Err(err) = result {
+        println!("{err}\n\n{USAGE}");
+        std::process::exit(1);
+    }
+}
+
+fn parse_texel(args: &[String]) -> Result<TexelOptions, String> {
+    let mut args = args.iter();
+    let mut options = TexelOptions::new(PathBuf::from(args.next().ok_or("No data file provided")?));
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--k" => options.k = Some(parse_value(arg, args.next())?),
+            "--lr" => options.learning_rate = parse_value(arg, args.next())?,
+            "--iterations" => options.max_iterations = parse_value(arg, args.next())?,
+            "--split" => options.validation_split = parse_value(arg, args.next())?,
+            "--resume" => options.resume = Some(parse_value(arg, args.next())?),
+            _ => return Err(format!("Unknown option '{arg}' for texel")),
+        }
+    }
+
+    if !(0. ..1.).contains(&options.validation_split) {
+        return Err("Validation split must be at least 0 and less than 1".to_string())
+    }
+
+    Ok(options)
+}
+
+fn parse_datagen(args: &[String]) -> Result<DatagenOptions, String> {
+    let mut args = args.iter();
+    let mut options = DatagenOptions::new(PathBuf::from(args.next().ok_or("No output file provided")?));
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--games" => options.games = parse_value(arg, args.next())?,
+            "--depth" => options.depth = parse_value(arg, args.next())?,
+            "--random" => options.random_plies = parse_value(arg, args.next())?,
+            _ => return Err(format!("Unknown option '{arg}' for datagen")),
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_spsa(args: &[String]) -> Result<SpsaOptions, String> {
+    let mut args = args.iter();
+    let mut options = SpsaOptions::default();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--iterations" => options.iterations = parse_value(arg, args.next())?,
+            "--pairs" => options.pairs = parse_value(arg, args.next())?,
+            "--nodes" => options.nodes = parse_value(arg, args.next())?,
+            _ => return Err(format!("Unknown option '{arg}' for spsa")),
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_value<T: FromStr>(name: &str, value: Option<&String>) -> Result<T, String> {
+    let value = value.ok_or(format!("No value provided for '{name}'"))?;
+    value.parse::<T>().map_err(|_| format!("Illegal value '{value}' for '{name}'"))
 }
\ No newline at end of file
@@ -65,8 +65,8 @@ impl Visitor for GameParser {
         }
 
         let pos = cadabra::Position::from_fen(&self.prev_fen).unwrap();
-        let eval = pos.evaluate(CONST_EVALUATOR);
-        let q_sqore = quiescence(&pos, -INFINITY, INFINITY, 0, &mut self.context, CONST_EVALUATOR);
+        let eval = pos.evaluate(&CONST_EVALUATOR);
+        let q_sqore = quiescence(&pos, -INFINITY, INFINITY, 0, &mut self.context, &CONST_EVALUATOR);
 
         if eval != q_sqore {
             self.different_score += 1;
@@ -0,0 +1,154 @@
+use std::{fs::{self, File}, io::Write, time::Instant};
+use cadabra::*;
+use chrono::Local;
+use rand::{thread_rng, seq::SliceRandom, Rng};
+use rayon::prelude::*;
+
+pub const DEFAULT_ITERATIONS: usize = 1000;
+/// Default game pairs played pr. iteration
+pub const DEFAULT_PAIRS: usize = 8;
+/// Default node limit pr. move. Unlike a fixed depth, it makes the engine that prunes less pay for the extra nodes
+pub const DEFAULT_NODES: u128 = 20_000;
+
+/// Random plies at the start of each game pair
+const RANDOM_PLIES: usize = 8;
+/// Games longer than this are adjudicated as draws
+const MAX_GAME_PLIES: usize = 300;
+const HASH_MB: usize = 4;
+
+/// Learning rate at the end of the tuning, as in fishtest
+const R_END: f64 = 0.002;
+const ALPHA: f64 = 0.602;
+const GAMMA: f64 = 0.101;
+
+pub struct SpsaOptions {
+    pub iterations: usize,
+    pub pairs: usize,
+    pub nodes: u128,
+}
+
+impl Default for SpsaOptions {
+    fn default() -> Self {
+        Self {
+            iterations: DEFAULT_ITERATIONS,
+            pairs: DEFAULT_PAIRS,
+            nodes: DEFAULT_NODES,
+        }
+    }
+}
+
+/// A search parameter being tuned
+struct Param {
+    name: &'static str,
+    value: f64,
+    min: f64,
+    max: f64,
+    /// Perturbation size at the end of the tuning
+    c_end: f64,
+}
+
+/// Tunes the search parameters with SPSA.
+///
+/// Each iteration perturbs all parameters in a random direction, and plays game pairs between the two resulting engines.
+pub fn tune(options: SpsaOptions) {
+    let defaults = SearchParams::default();
+    let mut params: Vec<Param> = SearchParams::SPECS.iter().map(|(name, min, max)| Param {
+        name: *name,
+        value: defaults.get(name).unwrap() as f64,
+        min: *min as f64,
+        max: *max as f64,
+        c_end: ((max - min) as f64 / 20.).max(1.),
+    }).collect();
+
+    let now = Local::now().naive_local();
+    fs::create_dir_all("tuning_results").unwrap();
+    let mut file = File::create(format!("tuning_results/spsa_results_{}.txt", now.format("%Y-%m-%d %H.%M.%S"))).unwrap();
+
+    let big_a = options.iterations as f64 / 10.;
+    let before = Instant::now();
+
+    for k in 1..=options.iterations {
+        let c_k = 1. / (k as f64).powf(GAMMA) * (options.iterations as f64).powf(GAMMA);
+        let a_k = (big_a + options.iterations as f64).powf(ALPHA) / (big_a + k as f64).powf(ALPHA);
+
+        let deltas: Vec<f64> = params.iter().map(|_| if thread_rng().gen() { 1. } else { -1. }).collect();
+
+        let mut plus = defaults;
+        let mut minus = defaults;
+        for (param, delta) in params.iter().zip(&deltas) {
+            let c = param.c_end * c_k;
+            plus.set(param.name, (param.value + c * delta).round().clamp(param.min, param.max) as i32).unwrap();
+            minus.set(param.name, (param.value - c * delta).round().clamp(param.min, param.max) as i32).unwrap();
+        }
+
+        // Score of the plus engine, between -1 and 1 pr. pair
+        let result: f64 = (0..options.pairs).into_par_iter().map(|_| play_pair(plus, minus, options.nodes)).sum();
+
+        for (param, delta) in params.iter_mut().zip(&deltas) {
+            let c = param.c_end * c_k;
+            let a = R_END * param.c_end * param.c_end * a_k;
+            param.value = (param.value + a / c * result * delta).clamp(param.min, param.max);
+        }
+
+        let values = params.iter().map(|p| format!("{} {:.2}", p.name, p.value)).collect::<Vec<_>>().join(", ");
+        let msg = format!("Iteration {k}:   Result: {result:+}   Total time elapsed: {}   {values}", pretty_duration::pretty_duration(&before.elapsed(), None));
+        println!("{msg}");
+        writeln!(file, "{msg}").unwrap();
+    }
+
+    println!("\nTuned parameters:");
+    writeln!(file, "\nTuned parameters:").unwrap();
+    for param in &params {
+        let line = format!("setoption name {} value {}", param.name, param.value.round());
+        println!("{line}");
+        writeln!(file, "{line}").unwrap();
+    }
+}
+
+/// Plays a game pair from a random opening with each engine as white once.
+///
+/// Returns the score of the first engine divided by the games played, from -1 to 1
+fn play_pair(first: SearchParams, second: SearchParams, nodes: u128) -> f64 {
+    let mut opening = Position::start_pos();
+    for _ in 0..RANDOM_PLIES {
+        let moves: Vec<_> = opening.generate_moves().collect();
+        match moves.choose(&mut thread_rng()) {
+            Some(m) => opening.make_move(*m),
+            None => return play_pair(first, second, nodes),
+        }
+    }
+
+    let white_result = play_game(opening, first, second, nodes);
+    let black_result = play_game(opening, second, first, nodes);
+
+    (white_result - black_result) / 2.
+}
+
+/// Plays a single game. Returns 1 if white wins, -1 if black wins and 0 on a draw
+fn play_game(mut pos: Position, white: SearchParams, black: SearchParams, nodes: u128) -> f64 {
+    let args = SearchArgs::new(None, false, false, None, None, None, Some(nodes), None).unwrap();
+    let white_search = Search::new(Settings::default().transposition_table_mb(HASH_MB).search_params(white));
+    let black_search = Search::new(Settings::default().transposition_table_mb(HASH_MB).search_params(black));
+
+    for _ in 0..MAX_GAME_PLIES {
+        if pos.generate_moves().len() == 0 {
+            return match (pos.is_in_check(), pos.active_color.is_white()) {
+                (true, true) => -1.,
+                (true, false) => 1.,
+                (false, _) => 0.,
+            }
+        }
+
+        if pos.half_moves >= 100 || pos.rep_table.is_in_3_fold_rep(&pos) || pos.is_insufficient_material() {
+            return 0.
+        }
+
+        let search = if pos.active_color.is_white() { &white_search } else { &black_search };
+        match search.start(pos, args, false, CONST_EVALUATOR).best_move {
+            Some(m) => pos.make_move(m),
+            None => return 0.,
+        }
+    }
+
+    0.
+}
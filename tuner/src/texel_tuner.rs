@@ -1,43 +1,150 @@
-use std::{io::{BufReader, BufRead, Write}, fs::{File, OpenOptions}, path::PathBuf, time::Instant/*, time::Instant*/};
+use std::{io::{BufReader, BufRead, Write}, fs::{File, OpenOptions}, path::PathBuf, time::Instant};
 use chrono::Local;
 use rayon::prelude::*;
 use term_size::*;
 use num_format::*;
 
-use cadabra::Position;
+use cadabra::{Position, Color, WeightArray, HeapEvaluator, WEIGHT_COUNT};
 
 use crate::{generate_fen_from_pgn, tuner_evaluator::TunerEvaluator};
 
-pub fn tune() {
-    let input = std::env::args().nth(1).unwrap_or_else(|| {
-        println!("No file provided!");
-        std::process::exit(1);
-    });
+/// Default step size of the Adam optimizer, in centipawns
+pub const DEFAULT_LEARNING_RATE: f64 = 1.;
+pub const DEFAULT_MAX_ITERATIONS: usize = 10000;
+/// Default fraction of the positions held out for validation
+pub const DEFAULT_VALIDATION_SPLIT: f64 = 0.1;
+
+const BETA1: f64 = 0.9;
+const BETA2: f64 = 0.999;
+const EPSILON: f64 = 1e-8;
+
+/// Print progress and save weights every this many iterations
+const REPORT_INTERVAL: usize = 50;
+/// Stop when the error improves less than this over a report interval
+const MIN_IMPROVEMENT: f64 = 1e-8;
+
+pub struct TexelOptions {
+    /// A .fen file, or a .pgn file to generate it from
+    pub data: PathBuf,
+    /// Use this K instead of searching for the best one
+    pub k: Option<f64>,
+    pub learning_rate: f64,
+    pub max_iterations: usize,
+    /// Fraction of the positions held out for validation
+    pub validation_split: f64,
+    /// Checkpoint file from 'tuning_results' to continue from
+    pub resume: Option<PathBuf>,
+}
 
-    let fen_file: PathBuf = if input.ends_with("pgn") {
-        // Generate fen file
-        let pgn_path = std::path::PathBuf::from(input.trim());
-        generate_fen_from_pgn(pgn_path)
-    } else if input.ends_with("fen") {
-        std::path::PathBuf::from(input.trim())
-    } else {
-        panic!("Invalid file type");
+impl TexelOptions {
+    pub fn new(data: PathBuf) -> Self {
+        Self {
+            data,
+            k: None,
+            learning_rate: DEFAULT_LEARNING_RATE,
+            max_iterations: DEFAULT_MAX_ITERATIONS,
+            validation_split: DEFAULT_VALIDATION_SPLIT,
+            resume: None,
+        }
+    }
+}
+
+/// A position reduced to the coefficients of the weights in the tapered evaluation.
+/// The evaluation is linear in the weights, so it is just a dot product.
+pub struct TuningEntry {
+    outcome: f64,
+    /// (weight index, coefficient), from white's perspective
+    coefficients: Vec<(u16, f32)>,
+    /// Score not covered by the weights
+    constant: f64,
+}
+
+impl TuningEntry {
+    pub fn new(outcome: f64, pos: &Position) -> Self {
+        let features = pos.eval_features();
+
+        let late = features.phase as f64 / 256.;
+        let early = 1. - late;
+
+        let mut coefficients: Vec<(u16, f32)> = features.features.iter().map(|f| {
+            let sign = if f.color == Color::White { 1. } else { -1. };
+            (f.index, (sign * (f.early as f64 * early + f.late as f64 * late)) as f32)
+        }).collect();
+
+        // Merge coefficients of the same weight
+        coefficients.sort_unstable_by_key(|(index, _)| *index);
+        let mut merged: Vec<(u16, f32)> = Vec::with_capacity(coefficients.len());
+        for (index, coefficient) in coefficients {
+            match merged.last_mut() {
+                Some(last) if last.0 == index => last.1 += coefficient,
+                _ => merged.push((index, coefficient)),
+            }
+        }
+        merged.retain(|(_, coefficient)| *coefficient != 0.);
+
+        Self {
+            outcome,
+            coefficients: merged,
+            constant: features.constant.0 as f64 * early + features.constant.1 as f64 * late,
+        }
+    }
+
+    /// Evaluation from white's perspective
+    fn evaluate(&self, weights: &[f64]) -> f64 {
+        self.constant + self.coefficients.iter().map(|(i, c)| weights[*i as usize] * *c as f64).sum::<f64>()
+    }
+}
+
+pub fn tune(options: TexelOptions) {
+    let fen_file: PathBuf = match options.data.extension().and_then(|e| e.to_str()) {
+        Some("pgn") => generate_fen_from_pgn(options.data), // Generate fen file
+        Some("fen") => options.data,
+        _ => {
+            println!("Invalid file type. Expected a .fen or .pgn file");
+            std::process::exit(1);
+        }
     };
-    
+
     println!("Tuning engine with '{}'", fen_file.display());
     // Time how long the calculation takes
     println!("Loading positions...");
     let start = Instant::now();
-    let positions = load_positions(fen_file);
+    let mut entries = load_entries(fen_file);
     let duration = start.elapsed();
     print!("\x1B[A");
-    println!("\rLoaded {} positions in: {duration:?}", positions.len().to_formatted_string(&Locale::en));
+    println!("\rLoaded {} positions in: {duration:?}", entries.len().to_formatted_string(&Locale::en));
+
+    // Hold out the last positions for validation. The fen files are already shuffled
+    let validation_count = (entries.len() as f64 * options.validation_split.clamp(0., 1.)) as usize;
+    let validation = entries.split_off(entries.len() - validation_count);
+    println!("Training on {} positions, validating on {}", entries.len().to_formatted_string(&Locale::en), validation.len().to_formatted_string(&Locale::en));
+
+    let initial_weights = match &options.resume {
+        Some(checkpoint) => match load_checkpoint(checkpoint) {
+            Ok(weights) => {
+                println!("Resuming from '{}'", checkpoint.display());
+                weights
+            },
+            Err(err) => {
+                println!("{err}");
+                std::process::exit(1);
+            }
+        },
+        None => TunerEvaluator::default().get_weights(),
+    };
+    let mut weights: Vec<f64> = initial_weights.iter().map(|w| *w as f64).collect();
 
-    println!("Calculating best k...");
     let now = Local::now().naive_local();
-    let k = find_k(&positions);
-    print!("\x1B[A");
-    println!("\rFound besk k: {}", k);
+    let k = match options.k {
+        Some(k) => k,
+        None => {
+            println!("Calculating best k...");
+            let k = find_k(&entries, &weights);
+            print!("\x1B[A");
+            k
+        }
+    };
+    println!("\rUsing k: {}", k);
 
     let folder_name = "tuning_results";
     let file_name = format!("tuning_results_{}", now.format("%Y-%m-%d %H.%M.%S").to_string());
@@ -51,69 +158,93 @@ pub fn tune() {
 
     let before = Instant::now();
 
-    let mut weights = TunerEvaluator::default().get_weights();
-    let mut delta = weights.into_iter().map(|_| 1).collect::<Vec<_>>();
+    writeln!(file, "Initial weights: {:?}", round_weights(&weights)).unwrap();
 
-    writeln!(file, "Initial weights: {:?}", weights).unwrap();
+    let mut best_err = mean_square_error(&entries, &weights, k);
+    let mut best_validation_err = mean_square_error(&validation, &weights, k);
+    let mut best_weights = weights.clone();
+    println!("Initial error: {}   Validation error: {}\n", best_err, best_validation_err);
 
-    //println!("Initial weights: {:?}", weights);
-    let mut best_err = mean_square_error(&positions, TunerEvaluator::default(), k);
-    println!("Initial error: {}\n", best_err);
+    // Adam moment estimates
+    let mut m = vec![0f64; WEIGHT_COUNT];
+    let mut v = vec![0f64; WEIGHT_COUNT];
 
     let mut iterations = 0;
+    let mut interval_start_err = best_err;
+    let mut in_before = Instant::now();
 
-    let mut improved = true;
-    while improved {
+    while iterations < options.max_iterations {
         iterations += 1;
-        improved = false;
-        let mut adjusted_weights = 0;
-        let in_before = Instant::now();
 
-        for w in 0..weights.len() {
-            let mut new_weights = weights.clone();
+        let grad = gradient(&entries, &weights, k);
 
-            let step = delta[w];
-            new_weights[w] += step;
-            let new_err = mean_square_error(&positions, TunerEvaluator::from_weights(new_weights), k);
+        let bias1 = 1. - BETA1.powi(iterations as i32);
+        let bias2 = 1. - BETA2.powi(iterations as i32);
+        for i in 0..WEIGHT_COUNT {
+            m[i] = BETA1 * m[i] + (1. - BETA1) * grad[i];
+            v[i] = BETA2 * v[i] + (1. - BETA2) * grad[i] * grad[i];
 
-            if new_err < best_err {
-                best_err = new_err;
-                weights = new_weights;
-                improved = true;
-                adjusted_weights += 1;
-            } else {
-                new_weights[w] -= 2 * step;
-                let new_error = mean_square_error(&positions, TunerEvaluator::from_weights(new_weights), k);
-                if new_error < best_err {
-                    best_err = new_error;
-                    weights = new_weights;
-                    improved = true;
-                    adjusted_weights += 1;
-                    delta[w] = -delta[w];
-                }
-            }
+            let m_hat = m[i] / bias1;
+            let v_hat = v[i] / bias2;
+            weights[i] -= options.learning_rate * m_hat / (v_hat.sqrt() + EPSILON);
+        }
 
-            let s = format!("Iteration {}:   Weight {w}/{}   Time: {}", iterations, weights.len(), pretty_duration::pretty_duration(&in_before.elapsed(), None));
-            let spaces = " ".repeat(dimensions_stdout().unwrap().0 - s.len());
-            print!("\x1B[A");
-            println!("\r{}{}", s, spaces);
-            std::io::stdout().flush().unwrap();
+        if iterations % REPORT_INTERVAL != 0 {
+            continue
         }
 
-        let s = format!("Iteration {}:   Adjusted {adjusted_weights}/{} weights.   Time: {}   Total time elapsed: {}.   Error: {}", iterations, weights.len(), pretty_duration::pretty_duration(&in_before.elapsed(), None), pretty_duration::pretty_duration(&before.elapsed(), None), best_err);
-        let spaces = " ".repeat(dimensions_stdout().unwrap().0 - s.len());
+        best_err = mean_square_error(&entries, &weights, k);
+        let validation_err = mean_square_error(&validation, &weights, k);
+
+        let s = format!("Iteration {}:   Time: {}   Total time elapsed: {}.   Error: {}   Validation error: {}", iterations, pretty_duration::pretty_duration(&in_before.elapsed(), None), pretty_duration::pretty_duration(&before.elapsed(), None), best_err, validation_err);
+        let spaces = " ".repeat(dimensions_stdout().map(|d| d.0).unwrap_or(0).saturating_sub(s.len()));
         print!("\x1B[A");
-        println!("\r{}{}\n", s, spaces);
+        println!("\r{}{}", s, spaces);
+        std::io::stdout().flush().unwrap();
 
         // Save weights to file
-        writeln!(file, "Iteration {iterations}: {:?}", weights).unwrap();
+        writeln!(file, "Iteration {iterations}: {:?}", round_weights(&weights)).unwrap();
+
+        if interval_start_err - best_err < MIN_IMPROVEMENT {
+            break
+        }
+
+        // Stop when overfitting
+        if !validation.is_empty() {
+            if validation_err > best_validation_err {
+                println!("Validation error increased, stopping\n");
+                break
+            }
+            best_validation_err = validation_err;
+        }
+        best_weights = weights.clone();
+
+        interval_start_err = best_err;
+        in_before = Instant::now();
     }
 
-    let msg = format!("Tuning finished in: {}. Error: {}", pretty_duration::pretty_duration(&before.elapsed(), None), best_err);
+    // Keep the last weights if they did not overfit
+    if validation.is_empty() || mean_square_error(&validation, &weights, k) <= best_validation_err {
+        best_weights = weights;
+    }
+
+    let best_err = mean_square_error(&entries, &best_weights, k);
+    let msg = format!("Tuning finished in: {} after {iterations} iterations. Error: {}   Validation error: {}", pretty_duration::pretty_duration(&before.elapsed(), None), best_err, mean_square_error(&validation, &best_weights, k));
     println!("{msg}");
     writeln!(file, "{msg}").unwrap();
 
-    writeln!(file, "\nFinal weights:\n{}", TunerEvaluator::from_weights(weights)).unwrap();
+    let final_weights = round_weights(&best_weights);
+    let evaluator = TunerEvaluator::from_weights(final_weights);
+    writeln!(file, "\nFinal weights:\n{}", evaluator).unwrap();
+
+    let consts_path = format!("{folder_name}/eval_consts.rs");
+    std::fs::write(&consts_path, format!("{evaluator}")).unwrap();
+    println!("Wrote weights to '{consts_path}'");
+
+    // Weights file that can be loaded by the engine through the EvalFile option
+    let weights_path = format!("{folder_name}/weights.json");
+    HeapEvaluator::from_weights(final_weights).save(&weights_path).unwrap();
+    println!("Wrote weights file to '{weights_path}'");
 }
 
 pub fn load_positions(fen_file: PathBuf) -> Vec<(f64, Position)> {
@@ -134,32 +265,92 @@ pub fn load_positions(fen_file: PathBuf) -> Vec<(f64, Position)> {
     positions
 }
 
-fn mean_square_error(positions: &Vec<(f64, Position)>, evaluator: TunerEvaluator, k: f64) -> f64 {
-    let n = positions.len() as usize;
-    let error: f64 = positions.par_iter().map(|(outcome, pos)| {
-        let mut score = pos.evaluate(evaluator) as f64;
-        if pos.active_color == cadabra::Color::Black { score *= -1.; } // Correct score to be independent of color
-        (outcome - sigmoid(score, k)).powi(2)
+/// Loads the positions and extracts their features.
+///
+/// Also verifies that the features reproduce the evaluation of the engine
+pub fn load_entries(fen_file: PathBuf) -> Vec<TuningEntry> {
+    let evaluator = TunerEvaluator::default();
+    let weights: Vec<f64> = evaluator.get_weights().iter().map(|w| *w as f64).collect();
+
+    let (entries, mismatches): (Vec<TuningEntry>, Vec<bool>) = load_positions(fen_file).par_iter().map(|(outcome, pos)| {
+        let entry = TuningEntry::new(*outcome, pos);
+
+        let mut score = pos.evaluate(&evaluator) as f64;
+        if pos.active_color == Color::Black { score *= -1.; } // Correct score to be independent of color
+        let mismatch = (entry.evaluate(&weights) - score).abs() > 1.;
+
+        (entry, mismatch)
+    }).unzip();
+
+    let mismatches = mismatches.into_iter().filter(|m| *m).count();
+    if mismatches > 0 {
+        println!("Warning: Features did not match the evaluation on {mismatches} positions\n");
+    }
+
+    entries
+}
+
+/// Reads the last saved weights from a tuning results file
+pub fn load_checkpoint(path: &PathBuf) -> Result<WeightArray, String> {
+    let content = std::fs::read_to_string(path).map_err(|err| format!("Could not read checkpoint '{}': {err}", path.display()))?;
+
+    let line = content.lines().rev()
+        .find(|l| l.starts_with("Iteration") || l.starts_with("Initial weights"))
+        .ok_or(format!("No weights found in checkpoint '{}'", path.display()))?;
+
+    let (_, list) = line.split_once(": ").ok_or("Malformed checkpoint line".to_string())?;
+    let weights = list.trim().trim_start_matches('[').trim_end_matches(']')
+        .split(',')
+        .map(|w| w.trim().parse::<i16>())
+        .collect::<Result<Vec<i16>, _>>()
+        .map_err(|_| "Checkpoint contained a weight that was not a number".to_string())?;
+
+    weights.try_into().map_err(|w: Vec<i16>| format!("Checkpoint had {} weights, expected {WEIGHT_COUNT}", w.len()))
+}
+
+fn mean_square_error(entries: &Vec<TuningEntry>, weights: &[f64], k: f64) -> f64 {
+    let n = entries.len().max(1);
+    let error: f64 = entries.par_iter().map(|entry| {
+        (entry.outcome - sigmoid(entry.evaluate(weights), k)).powi(2)
     }).sum();
 
     error / n as f64
 }
 
+/// The gradient of the mean square error with respect to each weight
+fn gradient(entries: &Vec<TuningEntry>, weights: &[f64], k: f64) -> Vec<f64> {
+    let n = entries.len();
+    let grad = entries.par_iter().fold(|| vec![0f64; WEIGHT_COUNT], |mut grad, entry| {
+        let s = sigmoid(entry.evaluate(weights), k);
+        let g = (s - entry.outcome) * s * (1. - s);
+        for (i, c) in entry.coefficients.iter() {
+            grad[*i as usize] += g * *c as f64;
+        }
+        grad
+    }).reduce(|| vec![0f64; WEIGHT_COUNT], |mut a, b| {
+        a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+        a
+    });
+
+    // d/ds of sigmoid is ln(10) * k / 400 * s * (1 - s)
+    let scale = 2. * std::f64::consts::LN_10 * k / 400. / n as f64;
+    grad.into_iter().map(|g| g * scale).collect()
+}
+
 /// Finds the k that minimizes the mean square error
-fn find_k(positions: &Vec<(f64, Position)>) -> f64 {
-    let evaluator = TunerEvaluator::default();
+fn find_k(entries: &Vec<TuningEntry>, weights: &[f64]) -> f64 {
     let mut best_k = 1f64;
-    let mut best_err = mean_square_error(positions, evaluator, best_k);
+    let mut best_err = mean_square_error(entries, weights, best_k);
     let mut step = 1f64;
     for _ in 0..10 {
         let mut new_k = best_k + step as f64;
-        let new_err = mean_square_error(positions, evaluator, new_k);
+        let new_err = mean_square_error(entries, weights, new_k);
         if new_err < best_err {
             best_err = new_err;
             best_k = new_k;
         } else {
             new_k = best_k - step as f64;
-            let new_err = mean_square_error(positions, evaluator, new_k);
+            let new_err = mean_square_error(entries, weights, new_k);
             if new_err < best_err {
                 best_err = new_err;
                 best_k = new_k;
@@ -172,6 +363,14 @@ fn find_k(positions: &Vec<(f64, Position)>) -> f64 {
     best_k
 }
 
+fn round_weights(weights: &[f64]) -> WeightArray {
+    let mut rounded = [0; WEIGHT_COUNT];
+    for (r, w) in rounded.iter_mut().zip(weights) {
+        *r = w.round() as i16;
+    }
+    rounded
+}
+
 fn sigmoid(s: f64, k: f64) -> f64 {
     1.0 / (1.0 + 10f64.powf(-k * s / 400.))
-}
\ No newline at end of file
+}
@@ -264,7 +264,7 @@ fn display_pst(table: [i16; 64], f: &mut std::fmt::Formatter<'_>) -> std::fmt::R
         write!(f, "    ")?;
         for j in 0..8 {
             let s = format!("{}", table[i * 8 + j]);
-            write!(f, "{},{}", &s, " ".repeat(5 - s.len()))?;
+            write!(f, "{},{}", &s, " ".repeat(5usize.saturating_sub(s.len())))?;
         }
         write!(f, "\n")?;
     }
@@ -0,0 +1,220 @@
+use std::{str::FromStr, time::{Instant, SystemTime, UNIX_EPOCH}};
+
+use cadabra::Position;
+use chess::{Board, MoveGen, Piece};
+
+use crate::TEST_POSITIONS;
+
+/// Default number of random games played
+pub const DEFAULT_GAMES: usize = 1000;
+/// Games are stopped after this many plies, if they have not ended before
+pub const DEFAULT_MAX_PLIES: usize = 300;
+
+pub struct FuzzOptions {
+    pub games: usize,
+    pub max_plies: usize,
+    /// The same seed plays the same games. A random seed is used if none is given
+    pub seed: Option<u64>,
+}
+
+impl Default for FuzzOptions {
+    fn default() -> Self {
+        Self {
+            games: DEFAULT_GAMES,
+            max_plies: DEFAULT_MAX_PLIES,
+            seed: None,
+        }
+    }
+}
+
+/// A game played on the engine and the reference move generator side by side
+struct Game {
+    pos: Position,
+    board: Board,
+    /// The positions since the last irreversible move, as the first 4 FEN fields, to count repetitions independently of the zobrist hash
+    history: Vec<String>,
+}
+
+impl Game {
+    fn new(fen: &str) -> Result<Self, String> {
+        let pos = Position::from_fen(fen).map_err(|err| format!("Could not parse FEN '{fen}': {err}"))?;
+        let board = Board::from_str(fen).map_err(|err| format!("Reference could not parse FEN '{fen}': {err:?}"))?;
+        Ok(Self { pos, board, history: vec![position_key(&pos)] })
+    }
+
+    /// The legal moves of the engine in UCI notation, sorted
+    fn moves(&self) -> Vec<String> {
+        let mut moves: Vec<String> = self.pos.generate_moves().map(|m| m.to_string()).collect();
+        moves.sort();
+        moves
+    }
+
+    fn make_move(&mut self, moove: &str) -> Result<(), String> {
+        let reference = MoveGen::new_legal(&self.board).find(|m| m.to_string() == moove).ok_or(format!("Reference does not have the move {moove}"))?;
+        self.board = self.board.make_move_new(reference);
+        self.pos.make_uci_move(moove)?;
+
+        if self.pos.half_moves == 0 {
+            self.history.clear();
+        }
+        self.history.push(position_key(&self.pos));
+        Ok(())
+    }
+
+    /// Compares the engine with the reference move generator, and with its own from-scratch computations
+    fn check(&self) -> Result<(), String> {
+        let moves = self.moves();
+        let mut reference: Vec<String> = MoveGen::new_legal(&self.board).map(|m| m.to_string()).collect();
+        reference.sort();
+        if moves != reference {
+            let missed: Vec<&String> = reference.iter().filter(|m| !moves.contains(m)).collect();
+            let extra: Vec<&String> = moves.iter().filter(|m| !reference.contains(m)).collect();
+            return Err(format!("Wrong legal moves. Missed {missed:?}, found too many {extra:?}"))
+        }
+
+        let mut scratch = self.pos;
+        scratch.generate_zobrist_hash();
+        if scratch.zobrist_hash != self.pos.zobrist_hash {
+            return Err(format!("Zobrist hash {:x} differs from the hash generated from scratch {:x}", self.pos.zobrist_hash, scratch.zobrist_hash))
+        }
+
+        let fen = self.pos.fen_string();
+        let parsed = Position::from_fen(&fen).map_err(|err| format!("FEN '{fen}' does not parse: {err}"))?;
+        if parsed.fen_string() != fen {
+            return Err(format!("FEN did not round-trip. Got {}", parsed.fen_string()))
+        }
+        if parsed.zobrist_hash != self.pos.zobrist_hash {
+            return Err("Zobrist hash differs after a FEN round-trip".to_string())
+        }
+
+        let reference_check = self.board.checkers().popcnt() > 0;
+        if self.pos.is_in_check() != reference_check {
+            return Err(format!("is_in_check was {}, expected {reference_check}", self.pos.is_in_check()))
+        }
+
+        let reference_insufficient = is_insufficient_material(&self.board);
+        if self.pos.is_insufficient_material() != reference_insufficient {
+            return Err(format!("is_insufficient_material was {}, expected {reference_insufficient}", self.pos.is_insufficient_material()))
+        }
+
+        let key = self.history.last().unwrap();
+        let reference_repetition = self.history.iter().filter(|k| *k == key).count() >= 3;
+        let repetition = self.pos.rep_table.is_in_3_fold_rep(&self.pos);
+        if repetition != reference_repetition {
+            return Err(format!("is_in_3_fold_rep was {repetition}, expected {reference_repetition}"))
+        }
+
+        Ok(())
+    }
+
+    /// Whether the game is over, or drawn by a rule the engine knows
+    fn is_over(&self) -> bool {
+        self.pos.generate_moves().len() == 0
+            || self.pos.half_moves >= 100
+            || self.pos.is_insufficient_material()
+            || self.pos.rep_table.is_in_3_fold_rep(&self.pos)
+    }
+}
+
+/// Plays random games from the test positions, and checks every position along the way.
+/// Returns an error with the shortest reproduction found, if a check fails
+pub fn fuzz(options: FuzzOptions) -> Result<(), String> {
+    let seed = options.seed.unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map_or(1, |d| d.as_nanos() as u64));
+    println!(" Fuzzing {} random games of at most {} plies with seed {seed}", options.games, options.max_plies);
+
+    let before = Instant::now();
+    let mut rng = Rng::new(seed);
+    let mut plies = 0;
+
+    for game_number in 1..=options.games {
+        let (_, start_fen, _) = TEST_POSITIONS[rng.next() as usize % TEST_POSITIONS.len()];
+        let mut game = Game::new(start_fen)?;
+        let mut moves: Vec<String> = Vec::new();
+
+        loop {
+            if let Err(err) = game.check() {
+                let (fen, line) = minimise(start_fen, &moves);
+                let line = if line.is_empty() { String::new() } else { format!(" moves {}", line.join(" ")) };
+                return Err(format!("Game {game_number} failed at ply {}: {err}\n Reproduce with:\n position fen {fen}{line}", moves.len()))
+            }
+
+            if game.is_over() || moves.len() >= options.max_plies {
+                break
+            }
+
+            let legal = game.moves();
+            let moove = legal[rng.next() as usize % legal.len()].clone();
+            game.make_move(&moove)?;
+            moves.push(moove);
+            plies += 1;
+        }
+
+        if game_number % 100 == 0 {
+            println!(" {game_number} games, {plies} plies checked");
+        }
+    }
+
+    println!(" Checked {plies} plies in {} games in {} ms", options.games, before.elapsed().as_millis());
+    Ok(())
+}
+
+/// Finds the latest position in the failing game that still fails when the remaining moves are played from its FEN,
+/// so the reproduction does not depend on more history than needed
+fn minimise(start_fen: &str, moves: &[String]) -> (String, Vec<String>) {
+    let mut fens = vec![start_fen.to_string()];
+    let mut game = Game::new(start_fen).unwrap();
+    for moove in moves {
+        game.make_move(moove).unwrap();
+        fens.push(game.pos.fen_string());
+    }
+
+    for start in (0..=moves.len()).rev() {
+        if replay(&fens[start], &moves[start..]).is_err() {
+            return (fens[start].clone(), moves[start..].to_vec())
+        }
+    }
+
+    (start_fen.to_string(), moves.to_vec())
+}
+
+/// Plays the moves from the FEN, checking every position
+fn replay(fen: &str, moves: &[String]) -> Result<(), String> {
+    let mut game = Game::new(fen)?;
+    game.check()?;
+    for moove in moves {
+        game.make_move(moove)?;
+        game.check()?;
+    }
+    Ok(())
+}
+
+/// The pieces, side to move, castling rights and en passant square. Positions with the same key are repetitions
+fn position_key(pos: &Position) -> String {
+    pos.fen_string().split_whitespace().take(4).collect::<Vec<&str>>().join(" ")
+}
+
+/// Each side has only the king, or the king and a single knight or bishop, like the engine defines it
+fn is_insufficient_material(board: &Board) -> bool {
+    let minors = *board.pieces(Piece::Knight) | *board.pieces(Piece::Bishop);
+    [chess::Color::White, chess::Color::Black].iter().all(|&color| {
+        let pieces = board.color_combined(color).popcnt();
+        pieces == 1 || (pieces == 2 && (minors & *board.color_combined(color)).popcnt() == 1)
+    })
+}
+
+/// Xorshift, so the games of a seed are the same on every platform
+struct Rng(u64);
+
+impl Rng {
+    fn new(seed: u64) -> Self {
+        // Xorshift must not be seeded with 0
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+}
@@ -1,11 +1,139 @@
 
+mod fuzz;
+mod suite;
+
 use std::{collections::HashMap, io::{stdout, Write}, str::FromStr};
 
 use cadabra::*;
 use chess::*;
+use fuzz::FuzzOptions;
+use suite::SuiteOptions;
+
+/// Size of the perft hash table used for deep perft
+const DEEP_PERFT_HASH_MB: usize = 256;
+
+const USAGE: &str = "Usage: validator [options]
+
+Without options, the move generator is validated on the test positions.
+
+Options:
+    --fuzz <games>        Play random games, checking every position against the reference move generator. Default: 1000
+        --plies <n>           Maximum plies pr. game. Default: 300
+        --seed <n>            Seed of the random games. Default: random
+    --suite <file>        Check the node counts of a perft EPD file, like 'rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - ;D1 20 ;D2 400'
+        --depth <n>           Maximum depth checked. Default: all depths in the file
+        --name <filter>       Only check positions with the filter in their id or FEN
+        --threads <n>         Threads used for perft. Default: all cores
+        --divide              Trace wrong node counts against the reference move generator";
 
 fn main() {
-    validate_move_gen();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+
+    let result = match args.first().map(|a| a.as_str()) {
+        None => {
+            validate_fen_round_trip();
+            validate_move_gen();
+            validate_deep_perft();
+            Ok(())
+        },
+        Some("--fuzz") => parse_fuzz(&args[1..]).map(|options| {
+            if let Err(err) = fuzz::fuzz(options) {
+                println!("{err}");
+                std::process::exit(1);
+            }
+        }),
+        Some("--suite") => parse_suite(&args[1..]).map(|options| {
+            if let Err(err) = suite::run_suite(options) {
+                println!("{err}");
+                std::process::exit(1);
+            }
+        }),
+        Some("help") | Some("--help") => {
+            println!("{USAGE}");
+            Ok(())
+        },
+        Some(unknown) => Err(format!("Unknown option '{unknown}'")),
+    };
+
+    if let Err(err) = result {
+        println!("{err}\n\n{USAGE}");
+        std::process::exit(1);
+    }
+}
+
+fn parse_fuzz(args: &[String]) -> Result<FuzzOptions, String> {
+    let mut args = args.iter().peekable();
+    let mut options = FuzzOptions::default();
+
+    // The number of games is optional
+    if let Some(games) = args.next_if(|arg| !arg.starts_with("--")) {
+        options.games = parse_value("--fuzz", Some(games))?;
+    }
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--plies" => options.max_plies = parse_value(arg, args.next())?,
+            "--seed" => options.seed = Some(parse_value(arg, args.next())?),
+            _ => return Err(format!("Unknown option '{arg}' for fuzz")),
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_suite(args: &[String]) -> Result<SuiteOptions, String> {
+    let mut args = args.iter();
+    let mut options = SuiteOptions {
+        path: parse_value("--suite", args.next())?,
+        max_depth: None,
+        name: None,
+        threads: available_threads(),
+        divide: false,
+    };
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--depth" => options.max_depth = Some(parse_value(arg, args.next())?),
+            "--name" => options.name = Some(parse_value(arg, args.next())?),
+            "--threads" => options.threads = parse_value::<usize>(arg, args.next())?.max(1),
+            "--divide" => options.divide = true,
+            _ => return Err(format!("Unknown option '{arg}' for suite")),
+        }
+    }
+
+    Ok(options)
+}
+
+fn available_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+fn parse_value<T: FromStr>(name: &str, value: Option<&String>) -> Result<T, String> {
+    let value = value.ok_or(format!("No value provided for '{name}'"))?;
+    value.parse::<T>().map_err(|_| format!("Illegal value '{value}' for '{name}'"))
+}
+
+/// Every test position must survive a FEN round-trip, also with move counters that do not fit in a byte
+fn validate_fen_round_trip() {
+    print!(" FEN round-trip ... ");
+    stdout().flush().unwrap();
+
+    for (name, fen, _) in TEST_POSITIONS.iter() {
+        let fields = fen.split_whitespace().take(4).collect::<Vec<&str>>().join(" ");
+        for counters in ["0 1", "99 300", "150 65535"] {
+            let fen = format!("{fields} {counters}");
+            let round_trip = Position::from_fen(&fen).unwrap().fen_string();
+            if round_trip != fen {
+                println!("Error at {name}:
+Expected {fen}
+Got      {round_trip}
+");
+                panic!("Validation failed");
+            }
+        }
+    }
+
+    println!("\tok")
 }
 
 fn debug_perft(pos: &Position, depth: u8) -> Result<HashMap<String, u64>, (String, Position)> {
@@ -16,7 +144,12 @@ fn debug_perft(pos: &Position, depth: u8) -> Result<HashMap<String, u64>, (Strin
     for m in moves {
         let mut copy = *pos;
         copy.make_move(m);
-        if copy.zobrist_hash != Position::from_fen(&copy.fen_string()).unwrap().zobrist_hash {
+        let fen = copy.fen_string();
+        let parsed = Position::from_fen(&fen).unwrap();
+        if parsed.fen_string() != fen {
+            return Err((format!("FEN did not round-trip after move {m}. Got {}", parsed.fen_string()), *pos))
+        }
+        if copy.zobrist_hash != parsed.zobrist_hash {
             return Err((format!("Wrong zobrist after move {m}"), *pos))
         }
         let sub_nodes = if depth >2 {
@@ -74,6 +207,28 @@ fn validate_move_gen() {
     println!("Validated all test positions")
 }
 
+/// Checks the node counts at depths too deep for the reference move generator, using hashed perft on all cores
+fn validate_deep_perft() {
+    let threads = available_threads();
+    let table = PerftTable::new(DEEP_PERFT_HASH_MB);
+
+    for (name, fen, depth, expected) in DEEP_PERFT_POSITIONS {
+        print!(" Deep perft {name} at depth {depth} ... ");
+        stdout().flush().unwrap();
+
+        let pos = Position::from_fen(fen).unwrap();
+        let nodes: u64 = pos.perft_divide(depth, threads, Some(&table)).iter().map(|(_, nodes)| nodes).sum();
+        if nodes != expected {
+            println!("Error at {name}:\nExpected {expected} nodes, got {nodes}\n");
+            panic!("Validation failed");
+        }
+
+        println!("\tok")
+    }
+
+    println!("Validated deep perft")
+}
+
 fn validate_position(fen: String, name: &str, depth: u8, tracing: bool) -> Result<(), (String, Position)> {
     let mut pos = Position::from_fen(fen.as_str()).unwrap();
     let own_res = debug_perft(&pos, depth)?;
@@ -107,6 +262,16 @@ fn validate_position(fen: String, name: &str, depth: u8, tracing: bool) -> Resul
     Ok(())
 }
 
+/// Known node counts from the Chess Programming Wiki
+pub const DEEP_PERFT_POSITIONS: [(&str, &str, u8, u64); 6] = [
+    ("Startpos", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 7, 3195901860),
+    ("Kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -", 6, 8031647685),
+    ("Position 3", "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - -", 8, 3009794393),
+    ("Position 4", "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1", 6, 706045033),
+    ("Position 5", "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", 5, 89941194),
+    ("Position 6", "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10", 5, 164075551),
+];
+
 pub const TEST_POSITIONS: [(&str, &str, u8); 56] = [
     ("Startpos", "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", 6),
     ("Kiwipete", "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq -", 5),
@@ -0,0 +1,152 @@
+use std::{io::{stdout, Write}, str::FromStr, time::Instant};
+
+use cadabra::{PerftTable, Position};
+use chess::Board;
+
+use crate::{debug_perft, ref_debug_perft, DEEP_PERFT_HASH_MB};
+
+pub struct SuiteOptions {
+    pub path: String,
+    /// Node counts deeper than this are skipped. All are checked if none is given
+    pub max_depth: Option<u8>,
+    /// Only positions with the filter in their name or FEN are checked
+    pub name: Option<String>,
+    pub threads: usize,
+    /// Trace wrong node counts against the reference move generator
+    pub divide: bool,
+}
+
+/// A position of a perft EPD file, with the expected node counts by depth
+struct SuitePosition {
+    name: String,
+    fen: String,
+    depths: Vec<(u8, u64)>,
+}
+
+/// Checks the node counts of the positions in a perft EPD file, like
+/// `rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1 ;D1 20 ;D2 400 ;D3 8902`
+pub fn run_suite(options: SuiteOptions) -> Result<(), String> {
+    let text = std::fs::read_to_string(&options.path).map_err(|err| format!("Could not read '{}': {err}", options.path))?;
+    let positions: Vec<SuitePosition> = read_positions(&text)?
+        .into_iter()
+        .filter(|p| options.name.as_ref().is_none_or(|filter| p.name.contains(filter.as_str()) || p.fen.contains(filter.as_str())))
+        .collect();
+
+    if positions.is_empty() {
+        return Err(format!("No positions in '{}' matched", options.path))
+    }
+
+    let before = Instant::now();
+    let table = PerftTable::new(DEEP_PERFT_HASH_MB);
+    let mut failed = 0;
+
+    for position in positions.iter() {
+        let pos = Position::from_fen(&position.fen).map_err(|err| format!("Could not parse FEN of {}: {err}", position.name))?;
+        let depths = position.depths.iter().filter(|(depth, _)| options.max_depth.is_none_or(|max| *depth <= max));
+
+        for &(depth, expected) in depths {
+            print!(" {} at depth {depth} ... ", position.name);
+            stdout().flush().unwrap();
+
+            let nodes: u64 = pos.perft_divide(depth, options.threads, Some(&table)).iter().map(|(_, nodes)| nodes).sum();
+            if nodes == expected {
+                println!("\tok");
+                continue
+            }
+
+            println!("\tfailed\n Expected {expected} nodes, got {nodes}");
+            if options.divide {
+                println!(" Divide against the reference move generator:");
+                if let Err(err) = print_divide_diff(&position.fen, depth, 1) {
+                    println!(" {err}");
+                }
+            }
+            failed += 1;
+
+            // The deeper counts of the position will be wrong as well
+            break
+        }
+    }
+
+    println!(" Checked {} positions in {} ms", positions.len(), before.elapsed().as_millis());
+    if failed > 0 {
+        return Err(format!("{failed} of {} positions failed", positions.len()))
+    }
+    Ok(())
+}
+
+/// Prints the moves where the engine and the reference move generator disagree, and follows the first wrong
+/// move down to the position where the move generation differs
+fn print_divide_diff(fen: &str, depth: u8, level: usize) -> Result<(), String> {
+    let indent = "  ".repeat(level);
+    let mut pos = Position::from_fen(fen).map_err(|err| format!("Could not parse FEN '{fen}': {err}"))?;
+    let own = debug_perft(&pos, depth).map_err(|(err, pos)| format!("{err} in {}", pos.fen_string()))?;
+    let reference = ref_debug_perft(Board::from_str(fen).map_err(|err| format!("Reference could not parse FEN '{fen}': {err:?}"))?, depth);
+
+    let mut moves: Vec<&String> = own.keys().chain(reference.keys()).collect();
+    moves.sort();
+    moves.dedup();
+
+    let mut first_difference = None;
+    for moove in moves {
+        match (own.get(moove), reference.get(moove)) {
+            (Some(own_nodes), Some(ref_nodes)) if own_nodes != ref_nodes => {
+                println!("{indent}{moove}: {own_nodes}, expected {ref_nodes}");
+                first_difference.get_or_insert(moove);
+            },
+            (Some(_), None) => println!("{indent}{moove}: illegal move generated"),
+            (None, Some(_)) => println!("{indent}{moove}: legal move missed"),
+            _ => {},
+        }
+    }
+
+    match first_difference {
+        Some(moove) if depth > 1 => {
+            pos.make_uci_move(moove)?;
+            println!("{indent}Tracing {moove} in {fen}");
+            print_divide_diff(&pos.fen_string(), depth - 1, level + 1)
+        },
+        Some(_) => Ok(()),
+        None if level == 1 => {
+            println!("{indent}The engine agrees with the reference move generator. The expected node count may be wrong");
+            Ok(())
+        },
+        None => Ok(()),
+    }
+}
+
+/// Reads the positions of a perft EPD file. Empty lines and lines starting with '#' are skipped.
+/// Positions are named by their `id` operation, or by their line number. Other operations are ignored
+fn read_positions(text: &str) -> Result<Vec<SuitePosition>, String> {
+    let mut positions = Vec::new();
+
+    for (index, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue
+        }
+
+        let mut operations = line.split(';');
+        let fen = operations.next().unwrap().trim().to_string();
+        let mut name = format!("Line {}", index + 1);
+        let mut depths = Vec::new();
+
+        for operation in operations.map(str::trim).filter(|o| !o.is_empty()) {
+            let (opcode, operand) = operation.split_once(char::is_whitespace).unwrap_or((operation, ""));
+            let operand = operand.trim();
+
+            if opcode == "id" {
+                name = operand.trim_matches('"').to_string();
+            } else if let Some(depth) = opcode.strip_prefix('D') {
+                let depth = depth.parse::<u8>().ok().filter(|d| *d > 0).ok_or(format!("Illegal depth '{opcode}' at line {}", index + 1))?;
+                let nodes = operand.parse::<u64>().map_err(|_| format!("Illegal node count '{operand}' at line {}", index + 1))?;
+                depths.push((depth, nodes));
+            }
+        }
+
+        depths.sort();
+        positions.push(SuitePosition { name, fen, depths });
+    }
+
+    Ok(positions)
+}